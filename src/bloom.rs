@@ -0,0 +1,53 @@
+use std::f64::consts::LN_2;
+
+// A Bloom filter over the stored hashes of the entries, kept by maps created with
+// `HashMap::with_bloom`. A clear bit proves the key was never placed, so lookups of absent keys
+// usually return without touching the buckets. Removing an entry doesn't clear its bits,
+// it just stays a false positive until the next resize or rehash rebuilds the filter
+pub(crate) struct Bloom {
+    bits: Vec<u64>,
+    // the number of bits set per hash
+    rounds: u32,
+}
+
+impl Bloom {
+    // A filter of `bits` bits, with as many rounds as suit `entries` expected entries
+    pub(crate) fn new(entries: usize, bits: usize) -> Bloom {
+        let bits = bits.max(64);
+        let rounds = (bits as f64 / entries.max(1) as f64 * LN_2).round() as u32;
+        Bloom {
+            bits: vec![0; bits.div_ceil(64)],
+            rounds: rounds.clamp(1, 16),
+        }
+    }
+
+    pub(crate) fn insert(&mut self, hash: u64) {
+        for bit in self.bit_indices(hash) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    // `false` means no entry with the hash was placed since the last `clear`
+    pub(crate) fn may_contain(&self, hash: u64) -> bool {
+        self.bit_indices(hash)
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    pub(crate) fn memory_usage(&self) -> usize {
+        self.bits.capacity() * std::mem::size_of::<u64>()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.bits.fill(0);
+    }
+
+    // Double hashing: both halves of the hash derive every round's bit
+    fn bit_indices(&self, hash: u64) -> impl Iterator<Item = usize> {
+        let len = self.bits.len() as u64 * 64;
+        let low = hash & 0xffff_ffff;
+        // odd, so the rounds don't repeat a bit early
+        let high = (hash >> 32) | 1;
+        (0..self.rounds as u64)
+            .map(move |round| (low.wrapping_add(round.wrapping_mul(high)) % len) as usize)
+    }
+}
@@ -0,0 +1,146 @@
+use std::hash::{BuildHasher, Hash};
+
+use super::{Bloom, HashMap, ResizeHook, SeededState};
+
+// Collects the settings of a map and creates it with `build`, instead of a constructor followed
+// by setters. Unset knobs keep the defaults of `HashMap::new`
+pub struct HashMapBuilder<S = SeededState> {
+    capacity: usize,
+    hash_builder: S,
+    load_factor: Option<f64>,
+    max_probe: Option<usize>,
+    on_resize: Option<ResizeHook>,
+    auto_shrink: bool,
+    compact: bool,
+    // the bits of the Bloom filter, `None` keeps none
+    bloom: Option<usize>,
+}
+
+impl HashMapBuilder {
+    pub fn new() -> HashMapBuilder {
+        HashMapBuilder {
+            capacity: HashMap::<(), ()>::DEFAULT_CAPACITY,
+            hash_builder: SeededState::default(),
+            load_factor: None,
+            max_probe: None,
+            on_resize: None,
+            auto_shrink: false,
+            compact: false,
+            bloom: None,
+        }
+    }
+}
+
+impl Default for HashMapBuilder {
+    fn default() -> HashMapBuilder {
+        HashMapBuilder::new()
+    }
+}
+
+impl<S> HashMapBuilder<S> {
+    // The initial number of buckets, see `HashMap::with_capacity`
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    // Hashes the keys with `hash_builder`, see `HashMap::with_hasher`
+    pub fn hasher<T>(self, hash_builder: T) -> HashMapBuilder<T> {
+        HashMapBuilder {
+            capacity: self.capacity,
+            hash_builder,
+            load_factor: self.load_factor,
+            max_probe: self.max_probe,
+            on_resize: self.on_resize,
+            auto_shrink: self.auto_shrink,
+            compact: self.compact,
+            bloom: self.bloom,
+        }
+    }
+
+    // Grows the map once the load factor would exceed `factor`, clamped to `0.1..=0.95`.
+    // Without one the map has a fixed capacity, see `HashMap::with_load_factor`
+    pub fn load_factor(mut self, factor: f64) -> Self {
+        self.load_factor = Some(factor.clamp(0.1, 0.95));
+        self
+    }
+
+    // Bounds the probe sequences, see `HashMap::set_max_probe`
+    pub fn max_probe(mut self, max_probe: usize) -> Self {
+        self.max_probe = Some(max_probe.max(1));
+        self
+    }
+
+    // See `HashMap::set_resize_hook`
+    pub fn resize_hook(mut self, hook: ResizeHook) -> Self {
+        self.on_resize = Some(hook);
+        self
+    }
+
+    // See `HashMap::auto_shrink`
+    pub fn auto_shrink(mut self, enabled: bool) -> Self {
+        self.auto_shrink = enabled;
+        self
+    }
+
+    // See `HashMap::with_capacity_compact`
+    pub fn compact(mut self, enabled: bool) -> Self {
+        self.compact = enabled;
+        self
+    }
+
+    // Keeps a Bloom filter of `bits` bits, see `HashMap::with_bloom`
+    pub fn bloom(mut self, bits: usize) -> Self {
+        self.bloom = Some(bits);
+        self
+    }
+
+    pub fn build<K, V>(self) -> HashMap<K, V, S>
+    where
+        K: Hash + Eq,
+        S: BuildHasher,
+    {
+        let mut map = HashMap::with_capacity_and_hasher(self.capacity, self.hash_builder);
+        map.max_load = self.load_factor;
+        map.max_probe = self.max_probe;
+        map.on_resize = self.on_resize;
+        map.auto_shrink = self.auto_shrink;
+        map.compact = self.compact;
+        map.bloom = self.bloom.map(|bits| Bloom::new(self.capacity, bits));
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::HashMapBuilder;
+    use crate::SeededState;
+    use std::hash::BuildHasher;
+
+    #[test]
+    fn test_builder() {
+        let mut m = HashMapBuilder::new()
+            .capacity(64)
+            .load_factor(0.5)
+            .hasher(SeededState::with_seed(9))
+            .build();
+        assert_eq!(m.cap(), 64);
+        for i in 0..32u32 {
+            m.insert(i, i);
+        }
+        assert_eq!(m.cap(), 64);
+        m.insert(32, 32);
+        assert_eq!(m.cap(), 128);
+        assert_eq!(m.hash_of(&1), SeededState::with_seed(9).hash_one(1u32));
+
+        // a fixed capacity map by default
+        let mut fixed = HashMapBuilder::new().capacity(4).max_probe(2).build();
+        for i in 0..8u32 {
+            fixed.insert(i, ());
+        }
+        assert!(fixed.len() <= 4);
+        assert_eq!(fixed.cap(), 4);
+        assert!(fixed.probe_stats().max_displacement < 2);
+    }
+}
@@ -0,0 +1,83 @@
+use std::hash::{BuildHasher, Hash};
+
+use super::{HashMap, SeededState};
+
+// A cursor over the entries in arbitrary order which can remove the entry it points at,
+// constructed from `HashMap::cursor_mut`. It walks the occupied index, a removal moves the last
+// unvisited entry into the current position, so every entry is still visited once
+pub struct CursorMut<'a, K: 'a, V: 'a, S = SeededState> {
+    map: &'a mut HashMap<K, V, S>,
+    // the position in the occupied index of the current entry
+    pos: usize,
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    // A cursor pointing at the first entry, if any
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, K, V, S> {
+        CursorMut { map: self, pos: 0 }
+    }
+}
+
+impl<K, V, S> CursorMut<'_, K, V, S> {
+    // The entry the cursor points at, `None` once it moved past the last one
+    pub fn current(&mut self) -> Option<(&K, &mut V)> {
+        let index = *self.map.occupied.get(self.pos)?;
+        self.map.buckets[index].pair_mut()
+    }
+
+    // Moves the cursor to the next entry
+    pub fn advance(&mut self) {
+        if self.pos < self.map.occupied.len() {
+            self.pos += 1;
+        }
+    }
+
+    // Removes the current entry like `HashMap::remove`, the cursor then points at the next entry
+    pub fn remove_current(&mut self) -> Option<(K, V)> {
+        if self.pos >= self.map.occupied.len() {
+            return None;
+        }
+        Some(self.map.remove_at(self.pos, true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::HashMap;
+
+    #[test]
+    fn test_cursor_mut() {
+        let mut m = HashMap::with_load_factor(0.75);
+        for i in 0..100u32 {
+            m.insert(i, i);
+        }
+
+        let mut cursor = m.cursor_mut();
+        let mut removed = Vec::new();
+        let mut keep = true;
+        while let Some((_, v)) = cursor.current() {
+            if keep {
+                *v += 1000;
+                cursor.advance();
+            } else {
+                removed.push(cursor.remove_current().unwrap().0);
+            }
+            keep = !keep;
+        }
+        assert_eq!(cursor.remove_current(), None);
+        assert_eq!(removed.len(), 50);
+
+        assert_eq!(m.len(), 50);
+        for k in removed {
+            assert!(!m.contains_key(&k));
+        }
+        for (&k, &v) in m.iter() {
+            assert_eq!(v, k + 1000);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash};
+use std::ops::{Deref, DerefMut};
+
+use super::HashMap;
+
+// A mutable borrow of a value which marks the entry dirty when it's dropped, if the value was
+// borrowed mutably through it. Constructed from `HashMap::get_mut_tracked`, the dirty entries
+// are collected by `HashMap::drain_dirty`, e.g. to write back only the changed entries of a cache
+pub struct DirtyGuard<'a, V: 'a> {
+    value: &'a mut V,
+    // the word of the map's dirty bitmap holding the bit of the entry
+    word: &'a mut u64,
+    bit: u64,
+    written: bool,
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    // Like `get_mut`, but mutating the value through the guard marks the entry dirty
+    pub fn get_mut_tracked<Q>(&mut self, key: &Q) -> Option<DirtyGuard<'_, V>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let index = self.probe_key_bucket(key)?;
+        Some(DirtyGuard {
+            value: self.buckets[index].value_mut().unwrap(),
+            word: &mut self.dirty[index / 64],
+            bit: 1 << (index % 64),
+            written: false,
+        })
+    }
+
+    // The entries marked dirty since the last call, in arbitrary order. They are clean again
+    // afterwards
+    pub fn drain_dirty(&mut self) -> Vec<(&K, &V)> {
+        let mut dirty = Vec::new();
+        for &index in &self.occupied {
+            let word = &mut self.dirty[index / 64];
+            if *word & (1 << (index % 64)) != 0 {
+                *word &= !(1 << (index % 64));
+                dirty.push(index);
+            }
+        }
+        dirty
+            .into_iter()
+            .filter_map(|index| self.buckets[index].pair())
+            .collect()
+    }
+}
+
+impl<V> Deref for DirtyGuard<'_, V> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        self.value
+    }
+}
+
+impl<V> DerefMut for DirtyGuard<'_, V> {
+    fn deref_mut(&mut self) -> &mut V {
+        self.written = true;
+        self.value
+    }
+}
+
+impl<V> Drop for DirtyGuard<'_, V> {
+    fn drop(&mut self) {
+        if self.written {
+            *self.word |= self.bit;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::HashMap;
+
+    #[test]
+    fn test_dirty_tracking() {
+        let mut m = HashMap::with_load_factor(0.75);
+        m.insert("a", 1);
+        m.insert("b", 2);
+
+        *m.get_mut_tracked("a").unwrap() += 10;
+        // reading doesn't make an entry dirty
+        assert_eq!(*m.get_mut_tracked("b").unwrap(), 2);
+        assert!(m.get_mut_tracked("c").is_none());
+
+        assert_eq!(m.drain_dirty(), [(&"a", &11)]);
+        assert!(m.drain_dirty().is_empty());
+
+        // the flag follows the entry when the map grows and entries around it are removed
+        let mut m = HashMap::with_load_factor(0.9);
+        for i in 0..10u32 {
+            m.insert(i, i);
+        }
+        *m.get_mut_tracked(&5).unwrap() += 100;
+        for i in 10..1000u32 {
+            m.insert(i, i);
+        }
+        for i in (0..1000u32).filter(|&i| i != 5 && i % 3 != 0) {
+            m.remove(&i);
+        }
+        m.retain(|&k, _| k % 2 == 1);
+        assert_eq!(m.drain_dirty(), [(&5, &105)]);
+    }
+}
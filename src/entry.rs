@@ -0,0 +1,716 @@
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash};
+
+use super::{HashMap, Probe, SeededState, Slot, DEFAULT_LOAD_FACTOR};
+
+// A view into a single entry of the map, which is either occupied or vacant,
+// constructed from `HashMap::entry`
+pub enum Entry<'a, K: 'a, V: 'a, S = SeededState> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+// A view into an occupied entry, the key lives in `map.buckets[index]`
+pub struct OccupiedEntry<'a, K: 'a, V: 'a, S = SeededState> {
+    map: &'a mut HashMap<K, V, S>,
+    index: usize,
+}
+
+// A view into a vacant entry, holds the key and its hash until it's inserted,
+// `vacant` is where the probe for the key ended
+pub struct VacantEntry<'a, K: 'a, V: 'a, S = SeededState> {
+    map: &'a mut HashMap<K, V, S>,
+    vacant: Option<(usize, usize)>,
+    hash: u64,
+    key: K,
+}
+
+// A view into a single entry of the map looked up by a borrowed key,
+// constructed from `HashMap::entry_ref`
+pub enum EntryRef<'a, 'b, K: 'a, Q: ?Sized + 'b, V: 'a, S = SeededState> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntryRef<'a, 'b, K, Q, V, S>),
+}
+
+// A view into a vacant entry, the borrowed key is only converted into an owned one on insertion
+pub struct VacantEntryRef<'a, 'b, K: 'a, Q: ?Sized + 'b, V: 'a, S = SeededState> {
+    map: &'a mut HashMap<K, V, S>,
+    vacant: Option<(usize, usize)>,
+    hash: u64,
+    key: &'b Q,
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    // Gets the entry of the key for in-place manipulation. Unlike `insert`, an entry can't
+    // report overflow, so even a fixed capacity map grows to make room for a vacant entry
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        let hash = self.hash_of(&key);
+        self.hashed_entry(hash, key)
+    }
+
+    // Like `entry` with the hash already computed by `hash_of`, e.g. for a Bloom check, so the
+    // key isn't hashed again. A hash from anywhere else would misplace the key
+    pub fn entry_with_hash(&mut self, hash: u64, key: K) -> Entry<'_, K, V, S> {
+        debug_assert_eq!(
+            hash,
+            self.hash_of(&key),
+            "hash doesn't match `hash_of` the key"
+        );
+        self.hashed_entry(hash, key)
+    }
+
+    fn hashed_entry(&mut self, hash: u64, key: K) -> Entry<'_, K, V, S> {
+        match self.probe(hash, &key) {
+            Probe::Found(index) => Entry::Occupied(OccupiedEntry { map: self, index }),
+            Probe::Vacant(vacant) => Entry::Vacant(VacantEntry {
+                map: self,
+                vacant,
+                hash,
+                key,
+            }),
+        }
+    }
+
+    // Like `entry`, but probes with a borrowed key, which is only converted into an owned key
+    // when a vacant entry is inserted, e.g. `&str` for a `String` key doesn't allocate on a hit
+    pub fn entry_ref<'a, 'b, Q>(&'a mut self, key: &'b Q) -> EntryRef<'a, 'b, K, Q, V, S>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq + ToOwned<Owned = K>,
+    {
+        let hash = self.hash_of(key);
+        match self.probe(hash, key) {
+            Probe::Found(index) => EntryRef::Occupied(OccupiedEntry { map: self, index }),
+            Probe::Vacant(vacant) => EntryRef::Vacant(VacantEntryRef {
+                map: self,
+                vacant,
+                hash,
+                key,
+            }),
+        }
+    }
+
+    // Returns the value of the key, inserting the result of `f` first if the key is absent
+    pub fn get_or_insert_with<F>(&mut self, key: K, f: F) -> &mut V
+    where
+        F: FnOnce() -> V,
+    {
+        self.entry(key).or_insert_with(f)
+    }
+
+    // Like `get_or_insert_with`, but hands out a shared reference, for memo tables whose
+    // cached values callers shouldn't change
+    pub fn memoize<F>(&mut self, key: K, f: F) -> &V
+    where
+        F: FnOnce() -> V,
+    {
+        self.get_or_insert_with(key, f)
+    }
+
+    // Returns the value of the key, inserting the result of `f` first if the key is absent.
+    // When `f` fails its error is returned and the map is left untouched
+    pub fn get_or_try_insert_with<F, E>(&mut self, key: K, f: F) -> Result<&mut V, E>
+    where
+        F: FnOnce() -> Result<V, E>,
+    {
+        let hash = self.hash_of(&key);
+        let index = match self.probe(hash, &key) {
+            Probe::Found(index) => index,
+            Probe::Vacant(vacant) => {
+                let val = f()?;
+                self.insert_new(vacant, hash, key, val)
+            }
+        };
+        Ok(self.buckets[index].value_mut().unwrap())
+    }
+
+    // Returns the value of the key, inserting `default` first if the key is absent. The flag is
+    // true when the key was newly inserted
+    pub fn get_mut_or_insert(&mut self, key: K, default: V) -> (&mut V, bool) {
+        let hash = self.hash_of(&key);
+        let (index, inserted) = match self.probe(hash, &key) {
+            Probe::Found(index) => (index, false),
+            Probe::Vacant(vacant) => (self.insert_new(vacant, hash, key, default), true),
+        };
+        (self.buckets[index].value_mut().unwrap(), inserted)
+    }
+
+    // Applies `f` to the value of the key, or inserts `default` if the key is absent.
+    // Returns the value either way
+    pub fn modify_or_insert<F>(&mut self, key: K, f: F, default: V) -> &mut V
+    where
+        F: FnOnce(&mut V),
+    {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                entry.map.record_insert(entry.index);
+                entry.into_mut()
+            }
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    // Inserts or overwrites the value of the key, returns the current value along with the
+    // value it replaced
+    pub fn upsert(&mut self, key: K, val: V) -> (&mut V, Option<V>) {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => {
+                let old = entry.overwrite(val);
+                (entry.into_mut(), Some(old))
+            }
+            Entry::Vacant(entry) => (entry.insert(val), None),
+        }
+    }
+
+    // Returns the value of the key, inserting `V::default()` first if the key is absent
+    pub fn get_or_insert_default(&mut self, key: K) -> &mut V
+    where
+        V: Default,
+    {
+        self.get_or_insert_with(key, V::default)
+    }
+}
+
+impl<K> HashMap<K, usize>
+where
+    K: Hash + Eq,
+{
+    // Counts how often every key occurs. The map is sized for the lower bound of the iterator's
+    // size hint and grows with `DEFAULT_LOAD_FACTOR`
+    pub fn frequencies<I>(iter: I) -> HashMap<K, usize>
+    where
+        I: IntoIterator<Item = K>,
+    {
+        let iter = iter.into_iter();
+        let mut counts = HashMap::with_load_factor(DEFAULT_LOAD_FACTOR);
+        counts.reserve(iter.size_hint().0);
+        for key in iter {
+            *counts.entry(key).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+impl<'a, K, V, S> Entry<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    // Returns a reference to the key of this entry
+    #[inline]
+    pub fn key(&self) -> &K {
+        match *self {
+            Entry::Occupied(ref entry) => entry.key(),
+            Entry::Vacant(ref entry) => entry.key(),
+        }
+    }
+
+    // Inserts `default` if the entry is vacant, returns a mutable reference to the value
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    // Inserts the result of `default` if the entry is vacant, returns a mutable reference to the value.
+    // The map is only touched once `default` returns, so if it panics the map is left unchanged
+    pub fn or_insert_with<F>(self, default: F) -> &'a mut V
+    where
+        F: FnOnce() -> V,
+    {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    // Like `or_insert_with`, but `default` computes the value from a reference to the key
+    pub fn or_insert_with_key<F>(self, default: F) -> &'a mut V
+    where
+        F: FnOnce(&K) -> V,
+    {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                // the borrow of the key ends with the call, before the key moves into the bucket
+                let value = default(entry.key());
+                entry.insert(value)
+            }
+        }
+    }
+}
+
+impl<'a, K, V, S> OccupiedEntry<'a, K, V, S> {
+    // Returns a reference to the key stored in the map
+    #[inline]
+    pub fn key(&self) -> &K {
+        self.map.buckets[self.index].key().unwrap()
+    }
+
+    // Returns a reference to the value
+    #[inline]
+    pub fn get(&self) -> &V {
+        self.map.buckets[self.index].value().unwrap()
+    }
+
+    // Returns a mutable reference to the value, bound to the entry
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut V {
+        self.map.buckets[self.index].value_mut().unwrap()
+    }
+
+    // Converts the entry into a mutable reference to the value, bound to the map's lifetime
+    #[inline]
+    pub fn into_mut(self) -> &'a mut V {
+        self.map.buckets[self.index].value_mut().unwrap()
+    }
+
+    // Removes the entry like `HashMap::remove`, without probing again, and returns the value
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    // Removes the entry and returns the stored key along with the value
+    pub fn remove_entry(self) -> (K, V) {
+        let pos = self.map.occupied_at[self.index];
+        self.map.remove_at(pos, true)
+    }
+
+    // Replaces both the stored key and the value, returning the old ones, e.g. to swap in a
+    // canonical instance of the key. The new key must equal the old one, so the bucket and
+    // the stored hash stay valid
+    pub fn replace_entry(self, key: K, value: V) -> (K, V)
+    where
+        K: Eq,
+    {
+        debug_assert!(key == *self.key(), "replace_entry with a different key");
+        let old = std::mem::replace(&mut self.map.buckets[self.index], Slot::KeyPair(key, value));
+        self.map.record_insert(self.index);
+        old.into_pair().unwrap()
+    }
+
+    // Puts `val` in place of the value and records the overwrite, returns the old value
+    pub(crate) fn overwrite(&mut self, val: V) -> V {
+        let old = std::mem::replace(self.get_mut(), val);
+        self.map.record_insert(self.index);
+        old
+    }
+}
+
+impl<'a, K, V, S> VacantEntry<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    // Returns a reference to the key that would be used when inserting
+    #[inline]
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    // Inserts the key with `value`, returns a mutable reference to the value
+    pub fn insert(self, value: V) -> &'a mut V {
+        let index = self.map.insert_new(self.vacant, self.hash, self.key, value);
+        self.map.buckets[index].value_mut().unwrap()
+    }
+}
+
+impl<'a, K, Q, V, S> EntryRef<'a, '_, K, Q, V, S>
+where
+    K: Hash + Eq + Borrow<Q>,
+    Q: ?Sized + ToOwned<Owned = K>,
+    S: BuildHasher,
+{
+    // Returns a reference to the key of this entry
+    #[inline]
+    pub fn key(&self) -> &Q {
+        match *self {
+            EntryRef::Occupied(ref entry) => entry.key().borrow(),
+            EntryRef::Vacant(ref entry) => entry.key(),
+        }
+    }
+
+    // Inserts `default` if the entry is vacant, returns a mutable reference to the value
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            EntryRef::Occupied(entry) => entry.into_mut(),
+            EntryRef::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    // Inserts the result of `default` if the entry is vacant, returns a mutable reference to the value
+    pub fn or_insert_with<F>(self, default: F) -> &'a mut V
+    where
+        F: FnOnce() -> V,
+    {
+        match self {
+            EntryRef::Occupied(entry) => entry.into_mut(),
+            EntryRef::Vacant(entry) => entry.insert(default()),
+        }
+    }
+}
+
+impl<'a, K, Q, V, S> VacantEntryRef<'a, '_, K, Q, V, S>
+where
+    K: Hash + Eq,
+    Q: ?Sized + ToOwned<Owned = K>,
+    S: BuildHasher,
+{
+    // Returns a reference to the borrowed key
+    #[inline]
+    pub fn key(&self) -> &Q {
+        self.key
+    }
+
+    // Inserts an owned copy of the key with `value`, returns a mutable reference to the value.
+    // The owned key hashes like the borrowed one, so the hash is reused
+    pub fn insert(self, value: V) -> &'a mut V {
+        let index = self
+            .map
+            .insert_new(self.vacant, self.hash, self.key.to_owned(), value);
+        self.map.buckets[index].value_mut().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{Entry, EntryRef, HashMap};
+    use std::borrow::Borrow;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_entry_key() {
+        let mut m = HashMap::new();
+        m.insert("foo", 42);
+
+        let entry = m.entry("foo");
+        assert!(matches!(entry, Entry::Occupied(_)));
+        assert_eq!(*entry.key(), "foo");
+        assert_eq!(*entry.or_insert(0), 42);
+
+        let entry = m.entry("bar");
+        assert!(matches!(entry, Entry::Vacant(_)));
+        assert_eq!(*entry.key(), "bar");
+        *entry.or_insert(1) += 1;
+        assert_eq!(m.get("bar"), Some(&2));
+        assert_eq!(m.len(), 2);
+    }
+
+    #[test]
+    fn test_entry_grows_full_map() {
+        let mut m = HashMap::with_capacity(2);
+        m.insert(1, 10);
+        m.insert(2, 20);
+        assert_eq!(m.insert(3, 30), (false, None));
+
+        *m.entry(3).or_insert(0) += 30;
+        assert_eq!(m.cap(), 4);
+        assert_eq!(m.len(), 3);
+        for i in 1..=3 {
+            assert_eq!(m.get(&i), Some(&(i * 10)));
+        }
+    }
+
+    #[test]
+    fn test_get_or_insert_default() {
+        let words = ["apple", "bob", "avocado", "banana", "cherry"];
+        let mut groups: HashMap<char, Vec<&str>> = HashMap::new();
+        for word in words {
+            let first = word.chars().next().unwrap();
+            groups.get_or_insert_default(first).push(word);
+        }
+
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups.get(&'a'), Some(&vec!["apple", "avocado"]));
+        assert_eq!(groups.get(&'b'), Some(&vec!["bob", "banana"]));
+        assert_eq!(groups.get(&'c'), Some(&vec!["cherry"]));
+
+        let mut calls = 0;
+        *groups.get_or_insert_with('a', || {
+            calls += 1;
+            Vec::new()
+        }) = vec![];
+        assert_eq!(calls, 0);
+        assert_eq!(groups.get(&'a'), Some(&vec![]));
+    }
+
+    #[test]
+    fn test_or_insert_with_key() {
+        let mut m: HashMap<String, usize> = HashMap::new();
+        assert_eq!(
+            *m.entry("foo".to_string()).or_insert_with_key(|k| k.len()),
+            3
+        );
+        assert_eq!(
+            *m.entry("quux".to_string()).or_insert_with_key(|k| k.len()),
+            4
+        );
+
+        // an occupied entry keeps its value, the closure isn't called
+        *m.get_mut("foo").unwrap() = 10;
+        let v = m
+            .entry("foo".to_string())
+            .or_insert_with_key(|_| unreachable!());
+        assert_eq!(*v, 10);
+        assert_eq!(m.len(), 2);
+    }
+
+    #[test]
+    fn test_get_or_try_insert_with() {
+        let mut m: HashMap<&str, i32> = HashMap::new();
+
+        let res = m.get_or_try_insert_with("foo", || "42x".parse::<i32>());
+        assert!(res.is_err());
+        assert_eq!(m.len(), 0);
+        assert_eq!(m.get("foo"), None);
+
+        let res = m.get_or_try_insert_with("foo", || "42".parse::<i32>());
+        assert_eq!(res, Ok(&mut 42));
+        assert_eq!(m.len(), 1);
+
+        // an existing value is returned without calling the closure
+        let res = m.get_or_try_insert_with("foo", || "x".parse::<i32>());
+        assert_eq!(res, Ok(&mut 42));
+    }
+
+    thread_local! {
+        static TO_OWNED_CALLS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    // A borrowed key which counts how often it's converted into an owned key
+    #[derive(Debug, Hash, PartialEq, Eq)]
+    struct Name(String);
+
+    #[derive(Debug, Hash, PartialEq, Eq)]
+    struct OwnedName(Name);
+
+    impl Borrow<Name> for OwnedName {
+        fn borrow(&self) -> &Name {
+            &self.0
+        }
+    }
+
+    impl ToOwned for Name {
+        type Owned = OwnedName;
+
+        fn to_owned(&self) -> OwnedName {
+            TO_OWNED_CALLS.with(|calls| calls.set(calls.get() + 1));
+            OwnedName(Name(self.0.clone()))
+        }
+    }
+
+    #[test]
+    fn test_entry_ref() {
+        let mut m = HashMap::new();
+        let foo = Name("foo".to_string());
+
+        let entry = m.entry_ref(&foo);
+        assert!(matches!(entry, EntryRef::Vacant(_)));
+        assert_eq!(entry.key(), &foo);
+        *entry.or_insert(0) += 1;
+        assert_eq!(TO_OWNED_CALLS.with(Cell::get), 1);
+
+        // the occupied path never converts the key
+        for _ in 0..10 {
+            let entry = m.entry_ref(&foo);
+            assert!(matches!(entry, EntryRef::Occupied(_)));
+            *entry.or_insert_with(|| unreachable!()) += 1;
+        }
+        assert_eq!(TO_OWNED_CALLS.with(Cell::get), 1);
+        assert_eq!(m.get(&foo), Some(&11));
+
+        // works with `str` for `String` keys
+        let mut m: HashMap<String, usize> = HashMap::new();
+        *m.entry_ref("bar").or_insert(0) += 1;
+        *m.entry_ref("bar").or_insert(0) += 1;
+        assert_eq!(m.get("bar"), Some(&2));
+    }
+
+    #[test]
+    fn test_get_mut_or_insert() {
+        let mut m = HashMap::new();
+
+        let (val, inserted) = m.get_mut_or_insert("hits", 0);
+        assert!(inserted);
+        *val += 1;
+
+        let (val, inserted) = m.get_mut_or_insert("hits", 100);
+        assert!(!inserted);
+        assert_eq!(*val, 1);
+        *val += 1;
+
+        assert_eq!(m.get("hits"), Some(&2));
+        assert_eq!(m.len(), 1);
+    }
+
+    #[test]
+    fn test_modify_or_insert() {
+        let mut counters = HashMap::new();
+        counters.insert("seen", 1);
+
+        let seen = counters.modify_or_insert("seen", |c| *c += 1, 1);
+        assert_eq!(*seen, 2);
+        *seen *= 10;
+
+        let fresh = counters.modify_or_insert("fresh", |c| *c += 1, 1);
+        assert_eq!(*fresh, 1);
+
+        assert_eq!(counters.get("seen"), Some(&20));
+        assert_eq!(counters.get("fresh"), Some(&1));
+    }
+
+    #[test]
+    fn test_occupied_entry() {
+        let mut m = HashMap::with_capacity(16);
+        let home = m.find_bucket(&0u64);
+        let keys: Vec<u64> = (0..).filter(|k| m.find_bucket(k) == home).take(3).collect();
+        for &k in &keys {
+            m.insert(k, k * 10);
+        }
+
+        match m.entry(keys[0]) {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(*entry.get(), keys[0] * 10);
+                *entry.get_mut() += 1;
+                assert_eq!(entry.remove(), keys[0] * 10 + 1);
+            }
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(m.len(), 2);
+        assert_eq!(m.get(&keys[0]), None);
+        // the chained keys shifted back and are still found
+        assert_eq!(m.get(&keys[1]), Some(&(keys[1] * 10)));
+        assert_eq!(m.get(&keys[2]), Some(&(keys[2] * 10)));
+
+        match m.entry(keys[2]) {
+            Entry::Occupied(entry) => assert_eq!(entry.remove_entry(), (keys[2], keys[2] * 10)),
+            Entry::Vacant(_) => unreachable!(),
+        }
+        match m.entry(keys[0]) {
+            Entry::Occupied(_) => unreachable!(),
+            Entry::Vacant(entry) => assert_eq!(*entry.insert(7), 7),
+        }
+        assert_eq!(m.len(), 2);
+        assert_eq!(m.get(&keys[1]), Some(&(keys[1] * 10)));
+    }
+
+    #[test]
+    fn test_frequencies() {
+        let counts = HashMap::frequencies("mississippi".chars());
+        assert_eq!(counts.len(), 4);
+        assert_eq!(counts.get(&'m'), Some(&1));
+        assert_eq!(counts.get(&'i'), Some(&4));
+        assert_eq!(counts.get(&'s'), Some(&4));
+        assert_eq!(counts.get(&'p'), Some(&2));
+        assert_eq!(counts.get(&'x'), None);
+
+        let words = HashMap::frequencies((0..1000).map(|i| i % 100));
+        assert_eq!(words.len(), 100);
+        assert!(words.values().all(|&n| n == 10));
+    }
+
+    #[test]
+    fn test_or_insert_with_unwind() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        // 8 entries fill 16 buckets up to the load factor, the 9th grows the map
+        let mut m = HashMap::with_load_factor(0.5);
+        for i in 0..8u32 {
+            m.insert(i, i);
+        }
+        let cap = m.cap();
+        assert_eq!(cap, 16);
+        assert!(m.would_resize(&100));
+
+        // the panic happens before the resize, it must leave the map as it was
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            m.entry(100).or_insert_with(|| panic!("no value"));
+        }));
+        assert!(result.is_err());
+        assert!(!m.contains_key(&100));
+        assert_eq!(m.len(), 8);
+        assert_eq!(m.cap(), cap);
+        assert_eq!(m.iter().count(), 8);
+
+        *m.entry(100).or_insert_with(|| 1) += 1;
+        assert_eq!(m.get(&100), Some(&2));
+        assert_eq!(m.len(), 9);
+        assert_eq!(m.cap(), 32);
+    }
+
+    #[test]
+    fn test_entry_with_hash() {
+        let mut a = HashMap::with_seed(3);
+        let mut b = HashMap::with_seed(3);
+        for i in 0..12u64 {
+            let hash = a.hash_of(&i);
+            *a.entry_with_hash(hash, i).or_insert(0) += i;
+            b.insert_std(i, i);
+        }
+        for i in 0..12u64 {
+            assert_eq!(a.bucket_index(&i), b.bucket_index(&i));
+            assert_eq!(a.get(&i), Some(&i));
+        }
+
+        let hash = a.hash_of(&5);
+        assert!(matches!(a.entry_with_hash(hash, 5), Entry::Occupied(_)));
+    }
+
+    #[test]
+    fn test_upsert() {
+        let mut m = HashMap::new();
+        let (val, old) = m.upsert("a", vec![1]);
+        assert_eq!(old, None);
+        val.push(2);
+
+        let (val, old) = m.upsert("a", vec![3]);
+        assert_eq!(old, Some(vec![1, 2]));
+        val.push(4);
+        assert_eq!(m.get("a"), Some(&vec![3, 4]));
+        assert_eq!(m.len(), 1);
+    }
+
+    #[test]
+    fn test_replace_entry() {
+        use std::rc::Rc;
+
+        let mut m = HashMap::new();
+        let first: Rc<str> = Rc::from("key");
+        m.insert(Rc::clone(&first), 1);
+
+        let canonical: Rc<str> = Rc::from("key");
+        let Entry::Occupied(entry) = m.entry(Rc::clone(&canonical)) else {
+            panic!("the key is present");
+        };
+        let (old_key, old_val) = entry.replace_entry(Rc::clone(&canonical), 2);
+        assert!(Rc::ptr_eq(&old_key, &first));
+        assert_eq!(old_val, 1);
+
+        let (stored, val) = m.iter().next().unwrap();
+        assert!(Rc::ptr_eq(stored, &canonical));
+        assert_eq!(*val, 2);
+        assert_eq!(m.len(), 1);
+    }
+
+    #[test]
+    fn test_memoize() {
+        let mut m = HashMap::new();
+        let calls = Cell::new(0);
+        let compute = || {
+            calls.set(calls.get() + 1);
+            "expensive".to_string()
+        };
+
+        let first = m.memoize(7, compute) as *const String;
+        assert_eq!(m.memoize(7, compute), "expensive");
+        assert_eq!(m.memoize(7, compute) as *const String, first);
+        assert_eq!(calls.get(), 1);
+    }
+}
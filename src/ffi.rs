@@ -0,0 +1,133 @@
+// C bindings over a `HashMap<CString, i64>`, the map is handed out as an opaque pointer.
+// Every function tolerates null pointers and keys that aren't valid UTF-8 by reporting failure.
+// The crate builds as an rlib only, build a C library from it with
+// `cargo rustc --release --features ffi --crate-type cdylib` (or `--crate-type staticlib`)
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+
+use super::HashMap;
+
+pub type StringMap = HashMap<CString, i64>;
+
+// Borrows the key behind `key`, `None` if it's null or not valid UTF-8
+unsafe fn key_from_ptr<'a>(key: *const c_char) -> Option<&'a CStr> {
+    if key.is_null() {
+        return None;
+    }
+    let key = CStr::from_ptr(key);
+    key.to_str().ok()?;
+    Some(key)
+}
+
+/// Creates a map, returns an opaque handle for the other functions. The handle must be
+/// released with `hm_free` exactly once, and not be used afterwards.
+#[no_mangle]
+pub extern "C" fn hm_new() -> *mut StringMap {
+    Box::into_raw(Box::new(HashMap::with_load_factor(0.75)))
+}
+
+/// Inserts or updates `key`, returns false if a pointer is null or the key isn't valid UTF-8.
+///
+/// # Safety
+///
+/// `handle` must come from `hm_new`, `key` must be null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn hm_insert(handle: *mut StringMap, key: *const c_char, val: i64) -> bool {
+    let (map, key) = match (handle.as_mut(), key_from_ptr(key)) {
+        (Some(map), Some(key)) => (map, key),
+        _ => return false,
+    };
+    map.insert(key.to_owned(), val).0
+}
+
+/// Writes the value of `key` to `out`, returns false if the key is absent or an argument is invalid.
+///
+/// # Safety
+///
+/// `handle` must come from `hm_new`, `key` must be null or a NUL-terminated string,
+/// `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn hm_get(
+    handle: *const StringMap,
+    key: *const c_char,
+    out: *mut i64,
+) -> bool {
+    if out.is_null() {
+        return false;
+    }
+    let (map, key) = match (handle.as_ref(), key_from_ptr(key)) {
+        (Some(map), Some(key)) => (map, key),
+        _ => return false,
+    };
+    match map.get(key) {
+        Some(val) => {
+            *out = *val;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Removes `key`, returns false if the key is absent or an argument is invalid.
+///
+/// # Safety
+///
+/// `handle` must come from `hm_new`, `key` must be null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn hm_remove(handle: *mut StringMap, key: *const c_char) -> bool {
+    match (handle.as_mut(), key_from_ptr(key)) {
+        (Some(map), Some(key)) => map.remove(key),
+        _ => false,
+    }
+}
+
+/// Releases a map created by `hm_new`, a null handle is ignored.
+///
+/// # Safety
+///
+/// `handle` must come from `hm_new` and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn hm_free(handle: *mut StringMap) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::ptr;
+
+    #[test]
+    fn test_ffi_round_trip() {
+        let foo = CString::new("foo").unwrap();
+        let bar = CString::new("bar").unwrap();
+        let invalid = [0xffu8, 0xfe, 0];
+        let invalid = invalid.as_ptr() as *const c_char;
+
+        unsafe {
+            let handle = hm_new();
+            assert!(hm_insert(handle, foo.as_ptr(), 42));
+            assert!(hm_insert(handle, foo.as_ptr(), 43));
+            assert!(!hm_insert(handle, invalid, 1));
+            assert!(!hm_insert(handle, ptr::null(), 1));
+            assert!(!hm_insert(ptr::null_mut(), foo.as_ptr(), 1));
+
+            let mut out = 0;
+            assert!(hm_get(handle, foo.as_ptr(), &mut out));
+            assert_eq!(out, 43);
+            assert!(!hm_get(handle, bar.as_ptr(), &mut out));
+            assert!(!hm_get(handle, invalid, &mut out));
+            assert!(!hm_get(handle, foo.as_ptr(), ptr::null_mut()));
+
+            assert!(hm_remove(handle, foo.as_ptr()));
+            assert!(!hm_remove(handle, foo.as_ptr()));
+            assert!(!hm_get(handle, foo.as_ptr(), &mut out));
+
+            hm_free(handle);
+            hm_free(ptr::null_mut());
+        }
+    }
+}
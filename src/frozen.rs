@@ -0,0 +1,166 @@
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash};
+use std::slice::Iter;
+
+use super::{fold_hash, HashMap, SeededState};
+
+// An immutable map built once by `HashMap::freeze`. The table is a power of two at most 7/8 full,
+// without tombstones, so a lookup masks the hash and probes at most `max_displacement + 1`
+// buckets, and the lookup of an absent key usually stops early at a free bucket
+pub struct FrozenHashMap<K, V, S = SeededState> {
+    slots: Vec<Option<(K, V)>>,
+    length: usize,
+    // the farthest any entry lives from its ideal bucket
+    max_displacement: usize,
+    hash_builder: S,
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    // Seals the map into a compact read-only `FrozenHashMap` using the same hasher
+    pub fn freeze(self) -> FrozenHashMap<K, V, S> {
+        let length = self.len();
+        let capacity = (length * 8 / 7 + 1).next_power_of_two();
+        let mut slots = Vec::with_capacity(capacity);
+        slots.resize_with(capacity, || None);
+
+        let mut max_displacement = 0;
+        // the entries keep their stored hashes, no key is hashed again
+        let entries = self.buckets.into_iter().zip(self.hashes);
+        for (slot, hash) in entries {
+            let Some((key, val)) = slot.into_pair() else {
+                continue;
+            };
+            let mut index = frozen_bucket(hash, capacity);
+            let mut displacement = 0;
+            while slots[index].is_some() {
+                index = (index + 1) & (capacity - 1);
+                displacement += 1;
+            }
+            slots[index] = Some((key, val));
+            max_displacement = max_displacement.max(displacement);
+        }
+        FrozenHashMap {
+            slots,
+            length,
+            max_displacement,
+            hash_builder: self.hash_builder,
+        }
+    }
+}
+
+impl<K, V, S> FrozenHashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let mask = self.slots.len() - 1;
+        let mut index = frozen_bucket(self.hash_builder.hash_one(key), self.slots.len());
+        for _ in 0..=self.max_displacement {
+            match self.slots[index] {
+                None => return None,
+                Some((ref k, ref v)) if k.borrow() == key => return Some(v),
+                _ => index = (index + 1) & mask,
+            }
+        }
+        None
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn iter(&self) -> FrozenIter<'_, K, V> {
+        FrozenIter {
+            inner: self.slots.iter(),
+        }
+    }
+}
+
+// `capacity` is a power of two, so masking the hash picks the bucket
+fn frozen_bucket(hash: u64, capacity: usize) -> usize {
+    fold_hash(hash) & (capacity - 1)
+}
+
+pub struct FrozenIter<'a, K: 'a, V: 'a> {
+    inner: Iter<'a, Option<(K, V)>>,
+}
+
+impl<'a, K, V> Iterator for FrozenIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        self.inner
+            .find_map(|slot| slot.as_ref().map(|(k, v)| (k, v)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::HashMap;
+
+    #[test]
+    fn test_freeze() {
+        let mut m = HashMap::with_load_factor(0.5);
+        for i in 0..300u64 {
+            m.insert(i, i * 2);
+        }
+        for i in (0..300u64).step_by(3) {
+            m.remove(&i);
+        }
+
+        let frozen = m.freeze();
+        assert_eq!(frozen.len(), 200);
+        assert_eq!(frozen.slots.len(), 256);
+        for i in 0..300u64 {
+            if i % 3 == 0 {
+                assert!(!frozen.contains_key(&i));
+            } else {
+                assert_eq!(frozen.get(&i), Some(&(i * 2)));
+            }
+        }
+        assert!(!frozen.contains_key(&1000));
+
+        let mut pairs: Vec<_> = frozen.iter().map(|(&k, &v)| (k, v)).collect();
+        pairs.sort_unstable();
+        assert_eq!(pairs.len(), 200);
+        assert_eq!(pairs[0], (1, 2));
+
+        // a power of two number of entries still leaves free buckets
+        let mut m = HashMap::with_load_factor(0.75);
+        for i in 0..1024u64 {
+            m.insert(i, i);
+        }
+        let frozen = m.freeze();
+        assert_eq!(frozen.slots.len(), 2048);
+        assert!(frozen.max_displacement < 32);
+        assert!((0..1024u64).all(|i| frozen.get(&i) == Some(&i)));
+        assert!((1024..4096u64).all(|i| !frozen.contains_key(&i)));
+
+        let empty = HashMap::<u64, u64>::new().freeze();
+        assert!(empty.is_empty());
+        assert_eq!(empty.get(&1), None);
+        assert_eq!(empty.iter().count(), 0);
+    }
+}
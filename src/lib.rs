@@ -1,36 +1,276 @@
 use std::borrow::Borrow;
 use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::ops::Deref;
 use std::slice::{Iter, IterMut};
 
-pub struct HashMap<K, V> {
-    // buckets: [Entry<K,V>; capacity],
-    // Once initialized, it‘s capacity will not change, which is guaranteed by program logic
-    buckets: Vec<Entry<K, V>>,
+mod bloom;
+mod builder;
+mod cursor;
+mod dirty;
+mod entry;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod frozen;
+mod macros;
+mod niche;
+mod oplog;
+#[cfg(feature = "rayon")]
+pub mod par;
+mod peek;
+mod perfect;
+mod ttl;
+mod view;
+
+use bloom::Bloom;
+pub use builder::HashMapBuilder;
+pub use cursor::CursorMut;
+pub use dirty::DirtyGuard;
+pub use entry::{Entry, EntryRef, OccupiedEntry, VacantEntry, VacantEntryRef};
+pub use frozen::{FrozenHashMap, FrozenIter};
+pub use niche::{Niche, NicheHashMap};
+pub use oplog::Op;
+use oplog::OpLog;
+pub use peek::PeekMut;
+pub use perfect::{NoPerfectHash, PerfectHashMap};
+pub use ttl::{Clock, SystemClock, TtlHashMap};
+pub use view::View;
+
+// The load factor `capacity_for` plans for
+pub const DEFAULT_LOAD_FACTOR: f64 = 0.875;
+
+// Up to this capacity a compact map grows one bucket at a time
+const COMPACT_THRESHOLD: usize = 16;
+
+pub struct HashMap<K, V, S = SeededState> {
+    // buckets: [Slot<K,V>; capacity],
+    // Once initialized, it‘s capacity only changes when the map grows in `resize`
+    buckets: Vec<Slot<K, V>>,
     capacity: usize,
     // always <= capacity
     length: usize,
+    // One bit per bucket, set once the bucket holds an entry and cleared only when it becomes
+    // `Slot::Empty` again, so a clear bit ends a probe sequence without touching the bucket
+    used: Vec<u64>,
+    // One bit per bucket, set once its entry was changed through a `DirtyGuard` since the last
+    // `drain_dirty`. The bit moves along with the entry
+    dirty: Vec<u64>,
+    // The buckets holding live entries in no particular order, so iterating is O(length)
+    // instead of O(capacity). `occupied_at[i]` is the position of bucket `i` in `occupied`
+    occupied: Vec<usize>,
+    occupied_at: Vec<usize>,
+    // The probe sequence length of every used bucket, i.e. how far its entry lives from the
+    // ideal bucket. A deleted bucket keeps the length of its removed entry
+    psl: Vec<usize>,
+    // The hash of the entry in every occupied bucket, so moving an entry never hashes its key
+    hashes: Vec<u64>,
+    // The map grows once `length / capacity` would exceed it,
+    // `None` means a fixed capacity where `insert` fails when the map is full
+    max_load: Option<f64>,
+    // The most buckets a probe sequence examines, `None` means it may scan the whole map
+    max_probe: Option<usize>,
+    // Called with `(old_capacity, new_capacity)` after the capacity changed in `resize`
+    on_resize: Option<ResizeHook>,
+    // Whether removals shrink a sparse map, see `auto_shrink`
+    auto_shrink: bool,
+    // Whether a small map grows by single buckets, see `with_capacity_compact`
+    compact: bool,
+    // Lets lookups of absent keys skip the probe, only maps from `with_bloom` keep one
+    bloom: Option<Bloom>,
+    // The mutations recorded since `record_ops(true)`
+    recorder: Option<OpLog<K, V>>,
+    // Builds the hasher of every key
+    hash_builder: S,
+}
+
+type ResizeHook = Box<dyn FnMut(usize, usize) + Send + Sync>;
+
+// The default `BuildHasher`: `DefaultHasher` keyed by a seed, which is 0 unless the map is
+// created by `with_seed`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SeededState {
+    seed: u64,
+}
+
+impl SeededState {
+    pub fn with_seed(seed: u64) -> SeededState {
+        SeededState { seed }
+    }
+}
+
+impl BuildHasher for SeededState {
+    type Hasher = DefaultHasher;
+
+    fn build_hasher(&self) -> DefaultHasher {
+        let mut hasher = DefaultHasher::new();
+        hasher.write_u64(self.seed);
+        hasher
+    }
 }
 
 impl<K, V> HashMap<K, V>
 where
     K: Hash + Eq,
 {
-    // create a HashMap with default capacity 100
+    // The capacity of `new`, a power of two like the capacities the map grows to
+    pub const DEFAULT_CAPACITY: usize = 16;
+
+    // create a HashMap with default capacity `DEFAULT_CAPACITY`, which grows past
+    // `DEFAULT_LOAD_FACTOR` so no insert fails. Use `with_capacity` for a fixed capacity
     pub fn new() -> HashMap<K, V> {
-        HashMap::with_capacity(100)
+        HashMap::with_hasher(SeededState::default())
     }
 
     // create a HashMap with capacity
     pub fn with_capacity(capacity: usize) -> HashMap<K, V> {
-        let mut buckets = Vec::with_capacity(capacity);
-        for _ in 0..capacity {
-            buckets.push(Entry::Empty);
+        HashMap::with_capacity_and_hasher(capacity, SeededState::default())
+    }
+
+    // create a compact HashMap with exactly capacity buckets, for many tiny maps, e.g. nested ones.
+    // Below 16 buckets it grows by a single bucket at a time instead of doubling, so a full map
+    // never holds more buckets than entries. Past that it rounds up to a power of two and doubles
+    pub fn with_capacity_compact(capacity: usize) -> HashMap<K, V> {
+        let mut map = HashMap::with_capacity(capacity);
+        map.compact = true;
+        map
+    }
+
+    // create a HashMap with default capacity `DEFAULT_CAPACITY`, which grows when the load factor would exceed
+    // `factor`, the factor is clamped to `0.1..=0.95`
+    pub fn with_load_factor(factor: f64) -> HashMap<K, V> {
+        let mut map = HashMap::new();
+        map.max_load = Some(factor.clamp(0.1, 0.95));
+        map
+    }
+
+    // create a HashMap with default capacity `DEFAULT_CAPACITY` whose hasher is keyed by `seed`. Maps with the
+    // same seed place the same keys into the same buckets, so their layout is reproducible
+    pub fn with_seed(seed: u64) -> HashMap<K, V> {
+        HashMap::with_hasher(SeededState::with_seed(seed))
+    }
+
+    // create a HashMap with capacity which keeps a Bloom filter of `bits` bits over its keys,
+    // for lookup tables queried mostly with absent keys: `get` and `contains_key` answer most
+    // misses without probing. Removed keys stay in the filter until the next resize or `rehash`
+    pub fn with_bloom(capacity: usize, bits: usize) -> HashMap<K, V> {
+        let mut map = HashMap::with_capacity(capacity);
+        map.bloom = Some(Bloom::new(capacity, bits));
+        map
+    }
+
+    // The capacity to pass to `with_capacity` so `entries` entries fit without exceeding
+    // `DEFAULT_LOAD_FACTOR`, a power of two
+    pub fn capacity_for(entries: usize) -> usize {
+        HashMap::<K, V>::capacity_for_with_load(entries, DEFAULT_LOAD_FACTOR)
+    }
+
+    // Like `capacity_for`, for a map created with the load factor `factor`
+    pub fn capacity_for_with_load(entries: usize, factor: f64) -> usize {
+        let factor = factor.clamp(0.1, 0.95);
+        let mut capacity = 1;
+        while entries as f64 > capacity as f64 * factor {
+            capacity = double_capacity(capacity, max_capacity::<K, V>());
+        }
+        capacity
+    }
+
+    // Bulk loads pairs sorted by `hash_of` (of a map with the default hasher) into a map of
+    // capacity buckets, which grows if needed. A pair whose hash exceeds every hash before it
+    // can't have been seen yet, so it's placed right away, only the others are looked up. In
+    // sorted input that's just equal keys, which are adjacent. The last of equal keys wins.
+    // Debug builds assert the order, unsorted input in a release build is loaded correctly,
+    // only slower
+    pub fn from_sorted_by_hash<I>(iter: I, capacity: usize) -> HashMap<K, V>
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut map = HashMap::with_capacity(capacity);
+        let mut max_hash = None;
+        for (key, val) in iter {
+            let hash = map.hash_of(&key);
+            debug_assert!(
+                max_hash <= Some(hash),
+                "pairs aren't sorted by hash: {:x} after {:x}",
+                hash,
+                max_hash.unwrap_or_default()
+            );
+            if Some(hash) <= max_hash || map.cap() == 0 {
+                map.insert_std(key, val);
+            } else {
+                let home = map.home_bucket(hash);
+                map.insert_new(Some((home, 0)), hash, key, val);
+            }
+            max_hash = max_hash.max(Some(hash));
+        }
+        map
+    }
+
+    // Collects the pairs like `collect`, but a key seen twice is an error carrying the second
+    // occurrence, e.g. for strict parsing of untrusted input. The map grows with
+    // `DEFAULT_LOAD_FACTOR`
+    pub fn from_iter_unique<I>(iter: I) -> Result<HashMap<K, V>, DuplicateKey<K>>
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let iter = iter.into_iter();
+        let mut map = HashMap::with_load_factor(DEFAULT_LOAD_FACTOR);
+        map.reserve(iter.size_hint().0);
+        for (key, val) in iter {
+            let hash = map.hash_of(&key);
+            match map.probe(hash, &key) {
+                Probe::Found(_) => return Err(DuplicateKey(key)),
+                Probe::Vacant(vacant) => {
+                    map.insert_new(vacant, hash, key, val);
+                }
+            }
+        }
+        Ok(map)
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    // create a HashMap with default capacity `DEFAULT_CAPACITY` hashing the keys with `hash_builder`,
+    // which grows like `new`
+    pub fn with_hasher(hash_builder: S) -> HashMap<K, V, S> {
+        let mut map =
+            HashMap::with_capacity_and_hasher(HashMap::<K, V>::DEFAULT_CAPACITY, hash_builder);
+        map.max_load = Some(DEFAULT_LOAD_FACTOR);
+        map
+    }
+
+    // create a HashMap with capacity hashing the keys with `hash_builder`. Panics when the
+    // capacity exceeds what can ever be allocated, instead of aborting in the allocator
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> HashMap<K, V, S> {
+        let max = max_capacity::<K, V>();
+        if capacity > max {
+            panic!("requested capacity {} exceeds maximum {}", capacity, max);
         }
+        let mut buckets = Vec::with_capacity(capacity);
+        buckets.resize_with(capacity, || Slot::Empty);
         HashMap {
-            buckets: buckets,
-            capacity: capacity,
+            buckets,
+            capacity,
             length: 0,
+            used: vec![0; capacity.div_ceil(64)],
+            dirty: vec![0; capacity.div_ceil(64)],
+            occupied: Vec::new(),
+            occupied_at: vec![0; capacity],
+            psl: vec![0; capacity],
+            hashes: vec![0; capacity],
+            max_load: None,
+            max_probe: None,
+            on_resize: None,
+            auto_shrink: false,
+            compact: false,
+            bloom: None,
+            recorder: None,
+            hash_builder,
         }
     }
 
@@ -49,447 +289,3859 @@ where
         self.length == 0
     }
 
-    pub fn clear(&mut self) {
-        for i in 0..self.capacity {
-            self.buckets[i] = Entry::Empty;
+    // The current fill of the buckets, `len / cap`, 0 for a map without buckets
+    #[inline]
+    pub fn load_factor(&self) -> f64 {
+        if self.capacity == 0 {
+            return 0.0;
+        }
+        self.length as f64 / self.capacity as f64
+    }
+
+    // Reserves capacity for at least `additional` more entries, so that inserting them
+    // won't exceed the load factor. A fixed capacity map is allowed to be filled completely
+    pub fn reserve(&mut self, additional: usize) {
+        if self.fits(additional) {
+            return;
+        }
+        let max_load = self.max_load.unwrap_or(1.0);
+        let needed = self.len().saturating_add(additional) as f64;
+        let mut capacity = self.cap().max(1);
+        while needed > capacity as f64 * max_load {
+            capacity = self.grow_capacity(capacity);
+        }
+        self.resize(capacity);
+    }
+
+    // The capacity the map grows to when an entry doesn't fit anymore, double the current one
+    // (one more bucket for a small compact map) up to the largest allocatable table. Growing
+    // further only happens when `set_max_probe` rejects the placement at that capacity too
+    pub fn next_capacity(&self) -> usize {
+        let limit = max_capacity::<K, V>();
+        match self.cap() {
+            // an empty table grows to a single bucket first
+            0 => 1,
+            capacity if capacity > limit / 2 => limit,
+            capacity => self.grow_capacity(capacity),
+        }
+    }
+
+    // The capacity a table of `capacity` buckets grows to, see `next_capacity`
+    fn grow_capacity(&self, capacity: usize) -> usize {
+        let limit = max_capacity::<K, V>();
+        if self.compact && capacity < COMPACT_THRESHOLD {
+            return capacity + 1;
+        }
+        if self.compact && !capacity.is_power_of_two() && capacity.next_power_of_two() <= limit {
+            return capacity.next_power_of_two();
         }
+        double_capacity(capacity, limit)
+    }
+
+    // Whether `additional` more entries fit without exceeding the load factor
+    #[inline]
+    fn fits(&self, additional: usize) -> bool {
+        let max_load = self.max_load.unwrap_or(1.0);
+        self.len().saturating_add(additional) as f64 <= self.cap() as f64 * max_load
     }
 
-    // Hash the key to get a bucket index, index < self.capacity
-    fn find_bucket<Q: ?Sized>(&self, key: &Q) -> usize
+    // Whether `insert_std` of the key would resize the map first: `false` when the key is
+    // present, since only its value changes, or when a new entry fits below the load factor
+    // and within the probe limit. Lets a latency-critical caller `reserve` ahead of time instead
+    pub fn would_resize<Q>(&self, key: &Q) -> bool
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: ?Sized + Hash + Eq,
     {
-        let mut hasher = DefaultHasher::new();
-        key.hash(&mut hasher);
-        hasher.finish() as usize % self.cap()
+        match self.probe(self.hash_of(key), key) {
+            Probe::Found(_) => false,
+            Probe::Vacant(Some((bucket, psl))) => !self.fits(1) || !self.can_place(bucket, psl),
+            Probe::Vacant(None) => true,
+        }
     }
 
-    // Returns true when the bucket not overflow, otherwise returns false
-    pub fn insert(&mut self, key: K, val: V) -> (bool, Option<V>) {
-        let mut index = self.find_bucket(&key);
-        let old_entry = &mut self.buckets[index];
-        if let Entry::Empty = old_entry {
-            *old_entry = Entry::KeyPair(key, val);
-            self.length += 1;
-            return (true, None);
+    // Bounds how many buckets a probe sequence examines, so every entry lives at most
+    // `max_probe - 1` buckets away from its ideal bucket and lookups stop after `max_probe` steps.
+    // The map grows instead of forming longer chains, only `insert` on a fixed capacity map
+    // and `try_insert_bounded` fail rather than grow
+    pub fn set_max_probe(&mut self, max_probe: usize) {
+        self.max_probe = Some(max_probe.max(1));
+        // entries placed under the previous bound may be too far from their ideal buckets
+        self.rehash();
+    }
+
+    // Installs a hook called with `(old_capacity, new_capacity)` whenever the map is resized,
+    // e.g. to report undersized initial capacities. Replaces any previous hook
+    pub fn set_resize_hook(&mut self, hook: Box<dyn FnMut(usize, usize) + Send + Sync>) {
+        self.on_resize = Some(hook);
+    }
+
+    // With `enabled`, `remove`, `retain` and `drain_filter` shrink the map once less than 1/8 of
+    // the buckets are in use, halving the capacity while at most 1/4 would be. A long-lived map
+    // with churn then doesn't keep the buckets of its peak size. It never shrinks below
+    // `DEFAULT_CAPACITY`
+    pub fn auto_shrink(&mut self, enabled: bool) {
+        self.auto_shrink = enabled;
+        self.shrink_if_sparse();
+    }
+
+    // Shrinks the map after removals, if `auto_shrink` is enabled
+    fn shrink_if_sparse(&mut self) {
+        let min = HashMap::<K, V>::DEFAULT_CAPACITY;
+        if !self.auto_shrink || self.cap() <= min || self.len() * 8 >= self.cap() {
+            return;
         }
-        if let Entry::KeyPair(k, ref mut v) = old_entry {
-            if k == &key {
-                return (true, Some(std::mem::replace(v, val)));
-            }
+        let mut capacity = self.cap();
+        while capacity / 2 >= min && self.len() * 4 <= capacity / 2 {
+            capacity /= 2;
+        }
+        self.resize(capacity);
+    }
+
+    // The number of buckets a probe sequence may examine
+    #[inline]
+    fn probe_limit(&self) -> usize {
+        match self.max_probe {
+            Some(max_probe) => max_probe.min(self.cap()),
+            None => self.cap(),
         }
-        // When no bucket is available, inserts are not allowed
-        if self.len() >= self.cap() {
-            return (false, None);
+    }
+
+    // Removes all entries, keeping the capacity so the buckets can be reused. Only the buckets of
+    // the occupied index are touched, so it's O(len) rather than O(capacity). Tombstones stay
+    // until the next rehash, they don't hide any entry anymore
+    pub fn clear(&mut self) {
+        for &index in &self.occupied {
+            self.buckets[index] = Slot::Empty;
+            self.used[index / 64] &= !(1 << (index % 64));
+            self.dirty[index / 64] &= !(1 << (index % 64));
         }
-        // Resolve hash collision
-        loop {
-            index += 1;
-            index = index % self.cap();
-            let old_entry = &self.buckets[index];
-            if let Entry::Empty = old_entry {
-                self.buckets[index] = Entry::KeyPair(key, val);
-                self.length += 1;
-                break;
-            }
+        if let Some(log) = self.recorder.as_mut() {
+            log.push(Op::Clear);
+        }
+        self.occupied.clear();
+        self.length = 0;
+        if let Some(bloom) = self.bloom.as_mut() {
+            bloom.clear();
         }
-        (true, None)
     }
 
-    // Calculate hash of the key, and if there is a conflict, search backward in turn,
-    // return a `None` if there is no space else return a bucket index
-    fn probe_key_bucket<Q: ?Sized>(&self, key: &Q) -> Option<usize>
+    // Removes all entries like `clear`, moving the keys out, the values are dropped
+    pub fn drain_keys(&mut self) -> Vec<K> {
+        let mut keys = Vec::with_capacity(self.len());
+        for &index in &self.occupied {
+            let slot = std::mem::replace(&mut self.buckets[index], Slot::Empty);
+            self.used[index / 64] &= !(1 << (index % 64));
+            self.dirty[index / 64] &= !(1 << (index % 64));
+            keys.extend(slot.into_pair().map(|(k, _)| k));
+        }
+        if let Some(log) = self.recorder.as_mut() {
+            log.push(Op::Clear);
+        }
+        self.occupied.clear();
+        self.length = 0;
+        if let Some(bloom) = self.bloom.as_mut() {
+            bloom.clear();
+        }
+        keys
+    }
+
+    // Removes all entries and shrinks back to the default capacity of `new`, freeing the
+    // buckets of a grown map. The load factor, probe bound and resize hook are kept
+    pub fn reset(&mut self) {
+        self.clear();
+        self.resize(HashMap::<K, V>::DEFAULT_CAPACITY);
+    }
+
+    // Hash the key with the map's hasher, the full 64 bits before they're reduced to a bucket.
+    // Maps with equal hashers agree on it, e.g. to route keys to shards consistently
+    #[inline]
+    pub fn hash_of<Q>(&self, key: &Q) -> u64
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: ?Sized + Hash,
     {
-        let mut index = self.find_bucket(key);
-        let entry = &self.buckets[index];
+        self.hash_builder.hash_one(key)
+    }
 
-        if let Entry::Empty = entry {
-            return None;
+    // Hash the key to get a bucket index, index < self.capacity. The map itself works with
+    // the stored hashes, the tests use it to pick colliding keys
+    #[cfg(test)]
+    fn find_bucket<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.home_bucket(self.hash_of(key))
+    }
+
+    // The ideal bucket of an entry with the hash, masking is the same as the modulo for a
+    // power of two capacity, only cheaper
+    #[inline]
+    fn home_bucket(&self, hash: u64) -> usize {
+        let capacity = self.cap();
+        if capacity.is_power_of_two() {
+            fold_hash(hash) & (capacity - 1)
+        } else {
+            fold_hash(hash) % capacity
         }
-        if entry.key().unwrap().borrow() == key {
-            return Some(index);
+    }
+
+    // Returns true when the bucket not overflow, otherwise returns false.
+    // A map with a load factor grows before a new key would exceed it, so it never overflows.
+    // Kept for the existing callers, new code should prefer `insert_std`
+    pub fn insert(&mut self, key: K, val: V) -> (bool, Option<V>) {
+        if self.max_load.is_none() {
+            return match self.try_insert_bounded(key, val) {
+                Ok(old) => (true, old),
+                Err(_) => (false, None),
+            };
         }
-        // hash collision
-        let start_index = index;
-        loop {
-            index += 1;
-            index = index % self.cap();
-            if index == start_index {
-                break None;
+        (true, self.insert_std(key, val))
+    }
+
+    // Inserts like `std::collections::HashMap::insert`, returns the previous value of the key.
+    // Grows the map whenever the key doesn't fit, even a fixed capacity one, so it can't fail
+    pub fn insert_std(&mut self, key: K, val: V) -> Option<V> {
+        self.insert_indexed(key, val).0
+    }
+
+    // Inserts like `insert_std`, and also returns the bucket the entry lives in afterwards,
+    // e.g. to key auxiliary data by it. Like `bucket_index`, the index is only valid until the
+    // next insertion of a new key, removal, resize or rehash moves the entries
+    pub fn insert_indexed(&mut self, key: K, val: V) -> (Option<V>, usize) {
+        let hash = self.hash_of(&key);
+        match self.probe(hash, &key) {
+            Probe::Found(index) => {
+                let old = self.buckets[index].value_mut().unwrap();
+                let old = std::mem::replace(old, val);
+                self.record_insert(index);
+                (Some(old), index)
             }
-            let entry = &self.buckets[index];
-            if entry.key().unwrap().borrow() == key {
-                break Some(index);
+            Probe::Vacant(vacant) => (None, self.insert_new(vacant, hash, key, val)),
+        }
+    }
+
+    // Inserts the key only if it's absent and returns whether it did, an existing value is kept
+    // and `val` is dropped. Grows like `insert_std`
+    pub fn insert_if_absent(&mut self, key: K, val: V) -> bool {
+        let hash = self.hash_of(&key);
+        match self.probe(hash, &key) {
+            Probe::Found(_) => false,
+            Probe::Vacant(vacant) => {
+                self.insert_new(vacant, hash, key, val);
+                true
             }
         }
     }
 
-    // Returns a reference to the value corresponding to the key
-    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V>
+    // Inserts every pair like `extend`, the last value of a key wins, and counts how many
+    // keys were new and how many values were overwritten
+    pub fn insert_all<I>(&mut self, iter: I) -> InsertStats
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq,
+        I: IntoIterator<Item = (K, V)>,
     {
-        let pi = self.probe_key_bucket(key);
-        match pi {
-            None => None,
-            Some(i) => match &self.buckets[i] {
-                &Entry::Empty => None,
-                &Entry::KeyPair(_, ref val) => Some(val),
-            },
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        let mut stats = InsertStats::default();
+        for (k, v) in iter {
+            match self.insert_std(k, v) {
+                Some(_) => stats.updated += 1,
+                None => stats.inserted += 1,
+            }
         }
+        stats
     }
 
-    // Returns a mutable reference to the value corresponding to the key
-    pub fn get_mut<Q: ?Sized>(&mut self, key: &Q) -> Option<&mut V>
-    where
-        K: Borrow<Q>,
-        Q: Hash + Eq,
-    {
-        let pi = self.probe_key_bucket(key);
-        match pi {
-            None => None,
-            Some(i) => match &mut self.buckets[i] {
-                &mut Entry::Empty => None,
-                &mut Entry::KeyPair(_, ref mut val) => Some(val),
-            },
+    // Inserts without ever growing the map, returns the previous value of the key,
+    // or gives the rejected key-value pair back in `Err` when no bucket is available
+    pub fn try_insert_bounded(&mut self, key: K, val: V) -> Result<Option<V>, (K, V)> {
+        let hash = self.hash_of(&key);
+        match self.probe(hash, &key) {
+            Probe::Found(index) => {
+                let old = self.buckets[index].value_mut().unwrap();
+                let old = std::mem::replace(old, val);
+                self.record_insert(index);
+                Ok(Some(old))
+            }
+            // When no bucket is available, inserts are not allowed
+            Probe::Vacant(Some(start)) => {
+                let index = self.place_at(start, hash, key, val)?;
+                self.record_insert(index);
+                Ok(None)
+            }
+            Probe::Vacant(None) => Err((key, val)),
         }
     }
 
-    pub fn contains_key<Q: ?Sized>(&self, key: &Q) -> bool
+    // Calculate hash of the key, and if there is a conflict, search backward in turn,
+    // return a `None` if there is no space else return a bucket index
+    fn probe_key_bucket<Q>(&self, key: &Q) -> Option<usize>
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: ?Sized + Hash + Eq,
     {
-        self.get(key).is_some()
+        self.probe_hashed(self.hash_of(key), key)
     }
 
-    pub fn keys(&self) -> Keys<'_, K, V> {
-        Keys { inner: self.iter() }
+    // Like `probe_key_bucket` for a key whose hash is already known
+    fn probe_hashed<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        if self
+            .bloom
+            .as_ref()
+            .is_some_and(|bloom| !bloom.may_contain(hash))
+        {
+            return None;
+        }
+        match self.probe(hash, key) {
+            Probe::Found(index) => Some(index),
+            Probe::Vacant(_) => None,
+        }
     }
 
-    pub fn values(&self) -> Values<'_, K, V> {
-        Values { inner: self.iter() }
+    // Walks the probe sequence of the key once, finding either its bucket or, when it's absent,
+    // where placing it would start: the first tombstone it may reuse, or else the bucket
+    // the lookup stopped at
+    #[inline]
+    fn probe<Q>(&self, hash: u64, key: &Q) -> Probe
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.probe_counted(hash, key).0
     }
 
-    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> bool
+    // Like `probe`, also returns how many buckets the walk examined
+    #[inline]
+    fn probe_counted<Q>(&self, hash: u64, key: &Q) -> (Probe, usize)
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: ?Sized + Hash + Eq,
     {
-        let pi = self.probe_key_bucket(key);
-        match pi {
-            None => false,
-            Some(i) => {
-                self.buckets[i] = Entry::Empty;
-                self.length -= 1;
-                true
+        // a map created with no buckets holds no key, and has nowhere to place one
+        if self.cap() == 0 {
+            return (Probe::Vacant(None), 0);
+        }
+        let mut index = self.home_bucket(hash);
+        let mut tombstone = None;
+        let limit = self.probe_limit();
+        for psl in 0..limit {
+            // the probe sequence ends at an empty bucket, a deleted one doesn't stop it
+            if !self.is_used(index) {
+                return (Probe::Vacant(tombstone.or(Some((index, psl)))), psl + 1);
+            }
+            // Robin Hood placement never leaves the key behind a bucket closer to its ideal
+            // bucket than the key would be here
+            if self.psl[index] < psl {
+                return (Probe::Vacant(tombstone.or(Some((index, psl)))), psl + 1);
             }
+            match self.buckets[index] {
+                Slot::KeyPair(ref k, _) if k.borrow() == key => {
+                    return (Probe::Found(index), psl + 1)
+                }
+                // its removed entry was as far from its ideal bucket as the key would be,
+                // the key may still live further on though
+                Slot::Deleted if tombstone.is_none() => tombstone = Some((index, psl)),
+                // hash collision
+                _ => {}
+            }
+            index += 1;
+            index %= self.cap();
         }
+        (Probe::Vacant(tombstone), limit)
     }
 
-    // An iterator visiting all key-value pairs, the iterator element type is `(&'a K, &'a V)`
-    #[inline]
-    pub fn iter(&self) -> HashMapIter<K, V> {
-        HashMapIter {
-            inner: self.buckets.iter(),
-        }
+    // Places an absent key with Robin Hood hashing: walking from the ideal bucket, the entry
+    // takes over the bucket of any entry closer to its own ideal bucket, and the displaced
+    // entry continues the walk. This keeps the probe sequence lengths close to each other.
+    // Returns the bucket of the new entry, or gives the pair back when there is no free bucket
+    // within the probe limit, in which case nothing has moved
+    fn place(&mut self, hash: u64, key: K, val: V) -> Result<usize, (K, V)> {
+        self.place_at((self.home_bucket(hash), 0), hash, key, val)
     }
 
-    // An iterator visiting all key-value pairs in arbitrary order, with mutable references to the values,
-    // the iterator element type is `(&'a K, &'a mut V)`
-    #[inline]
-    pub fn iter_mut(&mut self) -> HashMapIterMut<K, V> {
-        HashMapIterMut {
-            inner: self.buckets.iter_mut(),
+    // Like `place`, but the walk starts at `bucket`, `psl` buckets from the ideal bucket,
+    // as found by `probe`. Every bucket before it keeps its entry anyway
+    fn place_at(
+        &mut self,
+        (bucket, psl): (usize, usize),
+        hash: u64,
+        key: K,
+        val: V,
+    ) -> Result<usize, (K, V)> {
+        if self.len() >= self.cap() || !self.can_place(bucket, psl) {
+            return Err((key, val));
+        }
+        if let Some(bloom) = self.bloom.as_mut() {
+            bloom.insert(hash);
+        }
+        let mut hash = hash;
+        let mut carried = (key, val);
+        // the new entry starts out clean, a displaced one keeps its dirty bit
+        let mut carried_dirty = false;
+        let mut psl = psl;
+        let mut index = bucket;
+        let mut placed = None;
+        loop {
+            let taken = self.is_used(index) && self.psl[index] > psl;
+            match self.buckets[index] {
+                Slot::KeyPair(..) if self.psl[index] < psl => {
+                    let (k, v) = carried;
+                    let displaced =
+                        std::mem::replace(&mut self.buckets[index], Slot::KeyPair(k, v));
+                    carried = displaced.into_pair().unwrap();
+                    hash = std::mem::replace(&mut self.hashes[index], hash);
+                    psl = std::mem::replace(&mut self.psl[index], psl);
+                    let displaced_dirty = self.is_dirty(index);
+                    self.set_dirty(index, carried_dirty);
+                    carried_dirty = displaced_dirty;
+                    placed.get_or_insert(index);
+                }
+                Slot::KeyPair(..) => {}
+                // a deleted bucket is only reused by an entry at least as far from its ideal
+                // bucket as the removed one, so lookups passing it may still stop early
+                _ if !taken => {
+                    let (k, v) = carried;
+                    self.occupy(index, hash, k, v);
+                    self.psl[index] = psl;
+                    self.set_dirty(index, carried_dirty);
+                    return Ok(*placed.get_or_insert(index));
+                }
+                _ => {}
+            }
+            index += 1;
+            index %= self.cap();
+            psl += 1;
         }
     }
-}
 
-impl<K, V> PartialEq for HashMap<K, V>
-where
-    K: Eq + Hash,
-    V: PartialEq,
-{
-    fn eq(&self, other: &HashMap<K, V>) -> bool {
-        if self.len() != other.len() {
-            return false;
+    // Walks like `place_at` without moving anything, whether a free bucket is found within the
+    // probe limit for an entry `psl` buckets past its ideal bucket at `bucket`
+    fn can_place(&self, bucket: usize, mut psl: usize) -> bool {
+        let mut index = bucket;
+        while psl < self.probe_limit() {
+            match self.buckets[index] {
+                Slot::Empty => return true,
+                Slot::Deleted if self.psl[index] <= psl => return true,
+                Slot::KeyPair(..) if self.psl[index] < psl => psl = self.psl[index],
+                _ => {}
+            }
+            index += 1;
+            index %= self.cap();
+            psl += 1;
         }
-
-        self.iter()
-            .all(|(key, value)| other.get(key).map_or(false, |v| *value == *v))
+        false
     }
-}
-
-impl<K, V> Eq for HashMap<K, V>
-where
-    K: Eq + Hash,
-    V: Eq,
-{
-}
-
-pub struct HashMapIter<'a, K: 'a, V: 'a> {
-    inner: Iter<'a, Entry<K, V>>,
-}
 
-impl<'a, K, V> Iterator for HashMapIter<'a, K, V> {
-    type Item = (&'a K, &'a V);
+    // Places an absent key like `place_new`, and records the insertion
+    fn insert_new(&mut self, vacant: Option<(usize, usize)>, hash: u64, key: K, val: V) -> usize {
+        let index = self.place_new(vacant, hash, key, val);
+        self.record_insert(index);
+        index
+    }
 
-    fn next(&mut self) -> Option<(&'a K, &'a V)> {
-        let mut n = self.inner.next();
-        loop {
-            match n {
-                Some(entry) if !entry.is_empty() => {
-                    return Some((entry.key().unwrap(), entry.value().unwrap()))
-                }
-                Some(..) => {
-                    n = self.inner.next();
-                }
-                None => {
-                    return None;
+    // Places an absent key, growing the map until it fits. Placement starts where the probe
+    // for the key ended in `vacant`, unless the map has to grow first, which moves everything.
+    // Returns the bucket of the new entry
+    fn place_new(&mut self, vacant: Option<(usize, usize)>, hash: u64, key: K, val: V) -> usize {
+        let mut pair = (key, val);
+        if let Some(start) = vacant {
+            if self.fits(1) {
+                match self.place_at(start, hash, pair.0, pair.1) {
+                    Ok(index) => return index,
+                    Err(rejected) => pair = rejected,
                 }
             }
         }
+        self.reserve(1);
+        loop {
+            match self.place(hash, pair.0, pair.1) {
+                Ok(index) => return index,
+                Err(rejected) => pair = rejected,
+            }
+            self.resize(self.grow_capacity(self.cap()));
+        }
     }
-}
 
-pub struct HashMapIterMut<'a, K: 'a, V: 'a> {
-    inner: IterMut<'a, Entry<K, V>>,
-}
+    // Rebuilds the buckets at the current capacity, which drops all tombstones and places
+    // every entry again starting from its ideal bucket
+    pub fn rehash(&mut self) {
+        self.resize(self.cap());
+    }
 
-impl<'a, K, V> Iterator for HashMapIterMut<'a, K, V> {
-    type Item = (&'a K, &'a mut V);
+    // Installs a new hasher and rebuilds the buckets with it, e.g. a freshly keyed one once
+    // `probe_stats` reveals colliding keys
+    pub fn rehash_with(&mut self, hash_builder: S) {
+        self.hash_builder = hash_builder;
+        for &index in &self.occupied {
+            if let Slot::KeyPair(ref k, _) = self.buckets[index] {
+                self.hashes[index] = self.hash_builder.hash_one(k);
+            }
+        }
+        self.rehash();
+    }
 
-    fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
-        let mut n = self.inner.next();
-        loop {
-            match n {
-                Some(&mut Entry::KeyPair(ref key, ref mut val)) => return Some((key, val)),
-                Some(..) => {
-                    n = self.inner.next();
-                }
-                None => {
-                    return None;
-                }
+    // Collects how far the entries are displaced from their ideal buckets
+    pub fn probe_stats(&self) -> ProbeStats {
+        let mut stats = ProbeStats::default();
+        for (index, slot) in self.buckets.iter().enumerate() {
+            if let Slot::KeyPair(..) = *slot {
+                let home = self.home_bucket(self.hashes[index]);
+                let displacement = (index + self.cap() - home) % self.cap();
+                stats.max_displacement = stats.max_displacement.max(displacement);
+                stats.total_displacement += displacement;
             }
         }
+        stats
     }
-}
 
-pub struct Keys<'a, K: 'a, V: 'a> {
-    inner: HashMapIter<'a, K, V>,
-}
+    // Groups the keys by their ideal bucket, yielding every ideal bucket of some key in ascending
+    // order along with the keys hashing there, for analysing how the keys cluster
+    pub fn chains(&self) -> impl Iterator<Item = (usize, Vec<&K>)> {
+        let mut chains: BTreeMap<usize, Vec<&K>> = BTreeMap::new();
+        for &index in &self.occupied {
+            if let Slot::KeyPair(ref k, _) = self.buckets[index] {
+                let home = self.home_bucket(self.hashes[index]);
+                chains.entry(home).or_default().push(k);
+            }
+        }
+        chains.into_iter()
+    }
 
-impl<'a, K, V> Iterator for Keys<'a, K, V> {
-    type Item = &'a K;
+    // The indices of the buckets without an entry, both empty ones and tombstones
+    pub fn free_slots(&self) -> impl Iterator<Item = usize> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| !matches!(slot, Slot::KeyPair(..)))
+            .map(|(index, _)| index)
+    }
 
-    #[inline]
-    fn next(&mut self) -> Option<&'a K> {
-        self.inner.next().map(|(k, _)| k)
+    // The number of tombstones left by `retain` and `drain_filter`, which the next resize or
+    // `rehash` drops
+    pub fn tombstone_count(&self) -> usize {
+        self.buckets.iter().filter(|slot| slot.is_deleted()).count()
     }
-}
 
-pub struct Values<'a, K: 'a, V: 'a> {
-    inner: HashMapIter<'a, K, V>,
+    // The heap bytes allocated by the map: the buckets and the arrays alongside them, the occupied
+    // index and the Bloom filter. Memory owned by the keys and values themselves, like the
+    // contents of a `String`, isn't included
+    pub fn memory_usage(&self) -> usize {
+        use std::mem::size_of;
+
+        self.buckets.capacity() * size_of::<Slot<K, V>>()
+            + self.used.capacity() * size_of::<u64>()
+            + self.dirty.capacity() * size_of::<u64>()
+            + self.occupied.capacity() * size_of::<usize>()
+            + self.occupied_at.capacity() * size_of::<usize>()
+            + self.psl.capacity() * size_of::<usize>()
+            + self.hashes.capacity() * size_of::<u64>()
+            + self.bloom.as_ref().map_or(0, Bloom::memory_usage)
+    }
+
+    // Panics with a description of the first broken invariant of the layout: the length
+    // matches the live entries, and every entry sits in an unbroken probe sequence from its
+    // ideal bucket where lookups find it. Meant to be asserted after every step while fuzzing
+    #[cfg(any(test, feature = "invariant-checks"))]
+    pub fn check_invariants(&self) {
+        let cap = self.cap();
+        assert!(
+            self.length <= cap,
+            "length {} exceeds capacity {}",
+            self.length,
+            cap
+        );
+        assert_eq!(
+            self.buckets.len(),
+            cap,
+            "bucket count differs from capacity"
+        );
+        let live = self
+            .buckets
+            .iter()
+            .filter(|slot| slot.pair().is_some())
+            .count();
+        assert_eq!(
+            self.length, live,
+            "length {} but {} live entries",
+            self.length, live
+        );
+        assert_eq!(
+            self.occupied.len(),
+            live,
+            "the occupied index lists {} buckets, {} are live",
+            self.occupied.len(),
+            live
+        );
+
+        for (index, slot) in self.buckets.iter().enumerate() {
+            let empty = matches!(slot, Slot::Empty);
+            assert_eq!(
+                self.is_used(index),
+                !empty,
+                "used bit of bucket {} disagrees with its slot",
+                index
+            );
+            let Some((k, _)) = slot.pair() else {
+                continue;
+            };
+            assert_eq!(
+                self.occupied[self.occupied_at[index]], index,
+                "bucket {} is missing from the occupied index",
+                index
+            );
+            let hash = self.hash_of(k);
+            assert_eq!(self.hashes[index], hash, "stale hash in bucket {}", index);
+            let home = self.home_bucket(hash);
+            let distance = (index + cap - home) % cap;
+            assert_eq!(
+                self.psl[index], distance,
+                "bucket {} records psl {}, it is {} from its ideal bucket {}",
+                index, self.psl[index], distance, home
+            );
+            if let Some(empty) = (0..distance)
+                .map(|step| (home + step) % cap)
+                .find(|&i| matches!(self.buckets[i], Slot::Empty))
+            {
+                panic!(
+                    "the entry in bucket {} lies past empty bucket {} of its probe sequence",
+                    index, empty
+                );
+            }
+            assert_eq!(
+                self.probe_key_bucket(k),
+                Some(index),
+                "the entry in bucket {} isn't found by a lookup",
+                index
+            );
+        }
+    }
+
+    // Collects the occupancy and probe lengths in one scan of the buckets
+    pub fn stats(&self) -> HashMapStats {
+        let mut stats = HashMapStats {
+            capacity: self.cap(),
+            len: self.len(),
+            load_factor: self.load_factor(),
+            ..HashMapStats::default()
+        };
+        let mut total_probe_length = 0;
+        for (index, slot) in self.buckets.iter().enumerate() {
+            match *slot {
+                Slot::KeyPair(..) => {
+                    let probe_length = self.psl[index] + 1;
+                    stats.max_probe_length = stats.max_probe_length.max(probe_length);
+                    total_probe_length += probe_length;
+                }
+                Slot::Deleted => stats.tombstones += 1,
+                Slot::Empty => {}
+            }
+        }
+        if !self.is_empty() {
+            stats.avg_probe_length = total_probe_length as f64 / self.len() as f64;
+        }
+        stats
+    }
+
+    // Rebuilds the buckets with the new capacity and places all entries again,
+    // doubling the capacity further while an entry can't be placed within `max_probe`
+    fn resize(&mut self, mut capacity: usize) {
+        debug_assert!(capacity >= self.len());
+        let old_capacity = self.cap();
+        let mut pending: Vec<(u64, K, V, bool)> = Vec::with_capacity(self.len());
+        let mut old_buckets = std::mem::take(&mut self.buckets);
+        let mut old_hashes = std::mem::take(&mut self.hashes);
+        let mut old_dirty = std::mem::take(&mut self.dirty);
+        loop {
+            let entries = old_buckets.into_iter().zip(old_hashes).enumerate();
+            pending.extend(entries.filter_map(|(index, (slot, hash))| {
+                let dirty = old_dirty[index / 64] & (1 << (index % 64)) != 0;
+                slot.into_pair().map(|(k, v)| (hash, k, v, dirty))
+            }));
+            let mut buckets = Vec::with_capacity(capacity);
+            buckets.resize_with(capacity, || Slot::Empty);
+            self.buckets = buckets;
+            self.capacity = capacity;
+            self.length = 0;
+            self.used = vec![0; capacity.div_ceil(64)];
+            self.dirty = vec![0; capacity.div_ceil(64)];
+            self.occupied.clear();
+            self.occupied_at = vec![0; capacity];
+            self.psl = vec![0; capacity];
+            self.hashes = vec![0; capacity];
+            // every entry is placed again, which drops the bits of removed keys
+            if let Some(bloom) = self.bloom.as_mut() {
+                bloom.clear();
+            }
+            while let Some((hash, k, v, dirty)) = pending.pop() {
+                match self.place(hash, k, v) {
+                    Ok(index) => self.set_dirty(index, dirty),
+                    Err((k, v)) => {
+                        pending.push((hash, k, v, dirty));
+                        break;
+                    }
+                }
+            }
+            if pending.is_empty() {
+                break;
+            }
+            old_buckets = std::mem::take(&mut self.buckets);
+            old_hashes = std::mem::take(&mut self.hashes);
+            old_dirty = std::mem::take(&mut self.dirty);
+            capacity = self.grow_capacity(capacity);
+        }
+        if let Some(log) = self.recorder.as_mut() {
+            log.push(Op::Resize(capacity));
+        }
+        if capacity != old_capacity {
+            if let Some(hook) = self.on_resize.as_mut() {
+                hook(old_capacity, capacity);
+            }
+        }
+    }
+
+    // Returns a reference to the value corresponding to the key
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let pi = self.probe_key_bucket(key);
+        match pi {
+            None => None,
+            Some(i) => match self.buckets[i] {
+                Slot::KeyPair(_, ref val) => Some(val),
+                _ => None,
+            },
+        }
+    }
+
+    // Like `get`, also returns how many buckets the lookup examined, e.g. to sample probe
+    // lengths. 0 when the Bloom filter rules the key out
+    pub fn get_probed<Q>(&self, key: &Q) -> (Option<&V>, usize)
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let hash = self.hash_of(key);
+        if self
+            .bloom
+            .as_ref()
+            .is_some_and(|bloom| !bloom.may_contain(hash))
+        {
+            return (None, 0);
+        }
+        match self.probe_counted(hash, key) {
+            (Probe::Found(index), steps) => (self.buckets[index].value(), steps),
+            (Probe::Vacant(_), steps) => (None, steps),
+        }
+    }
+
+    // Returns a mutable reference to the value corresponding to the key
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let pi = self.probe_key_bucket(key);
+        match pi {
+            None => None,
+            Some(i) => match self.buckets[i] {
+                Slot::KeyPair(_, ref mut val) => Some(val),
+                _ => None,
+            },
+        }
+    }
+
+    // Like `get`, but an absent key is a `KeyNotFound` error, which can be propagated with `?`
+    pub fn try_get<'q, Q>(&self, key: &'q Q) -> Result<&V, KeyNotFound<'q, Q>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.get(key).ok_or(KeyNotFound { key })
+    }
+
+    // Takes the value out and leaves `V::default()` in its place, the key stays in the map
+    pub fn take_value<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+        V: Default,
+    {
+        let index = self.probe_key_bucket(key)?;
+        let old = std::mem::take(self.buckets[index].value_mut().unwrap());
+        self.record_insert(index);
+        Some(old)
+    }
+
+    // Puts `val` in place of the value of a present key and returns the old value,
+    // an absent key isn't inserted
+    pub fn replace_value<Q>(&mut self, key: &Q, val: V) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let index = self.probe_key_bucket(key)?;
+        let old = std::mem::replace(self.buckets[index].value_mut().unwrap(), val);
+        self.record_insert(index);
+        Some(old)
+    }
+
+    // Returns the value of the key, or `default` when it's absent
+    pub fn get_or<'a, Q>(&'a self, key: &Q, default: &'a V) -> &'a V
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.get(key).unwrap_or(default)
+    }
+
+    // Returns the stored key along with a mutable reference to the value
+    pub fn get_pair_mut<Q>(&mut self, key: &Q) -> Option<(&K, &mut V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let index = self.probe_key_bucket(key)?;
+        match self.buckets[index] {
+            Slot::KeyPair(ref k, ref mut val) => Some((k, val)),
+            _ => None,
+        }
+    }
+
+    // Returns the value dereferenced, e.g. `&dyn Fn()` for a `Box<dyn Fn()>` value
+    pub fn get_deref<Q>(&self, key: &Q) -> Option<&V::Target>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+        V: Deref,
+    {
+        self.get(key).map(Deref::deref)
+    }
+
+    // Looks up every key of `keys` in turn, the result at position `i` is the value of `keys[i]`
+    pub fn get_many<'a, Q>(&'a self, keys: &[&Q]) -> Vec<Option<&'a V>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        keys.iter().map(|key| self.get(*key)).collect()
+    }
+
+    // Mutable references to the values of all `keys` at once, the value at position `i` belongs
+    // to `keys[i]`. All or nothing: a missing key or the same key twice is an error
+    pub fn get_many_mut<Q, const N: usize>(
+        &mut self,
+        keys: [&Q; N],
+    ) -> Result<[&mut V; N], GetManyError>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let mut indices = [0; N];
+        for (i, key) in keys.into_iter().enumerate() {
+            let index = self.probe_key_bucket(key).ok_or(GetManyError::Missing(i))?;
+            // equal keys live in the same bucket
+            if let Some(first) = indices[..i].iter().position(|&other| other == index) {
+                return Err(GetManyError::Duplicate(first, i));
+            }
+            indices[i] = index;
+        }
+        let slots = self
+            .buckets
+            .get_disjoint_mut(indices)
+            .expect("distinct buckets of live entries");
+        Ok(slots.map(|slot| slot.value_mut().unwrap()))
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.get(key).is_some()
+    }
+
+    // The bucket the key currently occupies, `None` if it's absent. Any resize or rehash moves
+    // the entries, so an index is only valid until the next one
+    pub fn bucket_index<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.probe_key_bucket(key)
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    // The keys present in both maps, walks `self` and probes `other`
+    pub fn intersection_keys<'a, W, T>(
+        &'a self,
+        other: &'a HashMap<K, W, T>,
+    ) -> impl Iterator<Item = &'a K>
+    where
+        T: BuildHasher,
+    {
+        self.keys().filter(move |k| other.contains_key(*k))
+    }
+
+    // The keys of `self` absent from `other`, walks `self` and probes `other`
+    pub fn difference_keys<'a, W, T>(
+        &'a self,
+        other: &'a HashMap<K, W, T>,
+    ) -> impl Iterator<Item = &'a K>
+    where
+        T: BuildHasher,
+    {
+        self.keys().filter(move |k| !other.contains_key(*k))
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let pi = self.probe_key_bucket(key);
+        match pi {
+            None => false,
+            Some(i) => {
+                self.remove_at(self.occupied_at[i], true);
+                self.shrink_if_sparse();
+                true
+            }
+        }
+    }
+
+    // Retains only the entries for which `f` returns true
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.retain_count(f);
+    }
+
+    // Same as `retain`, returns the number of entries removed
+    pub fn retain_count<F>(&mut self, mut f: F) -> usize
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let mut removed = 0;
+        let mut pos = 0;
+        while pos < self.occupied.len() {
+            let keep = match self.buckets[self.occupied[pos]] {
+                Slot::KeyPair(ref k, ref mut v) => f(k, v),
+                _ => unreachable!("the occupied index points at a free bucket"),
+            };
+            if keep {
+                pos += 1;
+            } else {
+                // the last entry of the index takes its place, and is examined next
+                self.remove_at(pos, false);
+                removed += 1;
+            }
+        }
+        self.shrink_if_sparse();
+        removed
+    }
+
+    // Removes the entries for which `pred` returns true and yields them as owned pairs.
+    // Dropping the iterator early still removes the remaining matches
+    pub fn drain_filter<F>(&mut self, pred: F) -> DrainFilter<'_, K, V, F, S>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        DrainFilter {
+            map: self,
+            pred,
+            pos: 0,
+        }
+    }
+
+    // An iterator visiting all key-value pairs, the iterator element type is `(&'a K, &'a V)`.
+    // The order is unspecified, use `iter_by_bucket` for the physical order
+    #[inline]
+    pub fn iter(&self) -> HashMapIter<'_, K, V> {
+        HashMapIter {
+            inner: Walk::Occupied(self.occupied.iter()),
+            buckets: &self.buckets,
+        }
+    }
+
+    // Like `iter`, but visits the buckets in order starting at `start_bucket`, for scans done in
+    // chunks: `HashMapIter::next_bucket` tells where to resume with the next call. The entries of
+    // a chunk are only guaranteed to be visited once if the map doesn't change in between
+    pub fn iter_from(&self, start_bucket: usize) -> HashMapIter<'_, K, V> {
+        let next = start_bucket.min(self.cap());
+        HashMapIter {
+            inner: Walk::Buckets {
+                next,
+                remaining: self.occupied.iter().filter(|&&index| index >= next).count(),
+            },
+            buckets: &self.buckets,
+        }
+    }
+
+    // An iterator visiting all key-value pairs in ascending order of their buckets. The order
+    // only depends on the hasher and the history of the map, e.g. for reproducible dumps
+    // while debugging a hasher
+    pub fn iter_by_bucket(&self) -> HashMapIter<'_, K, V> {
+        HashMapIter {
+            inner: Walk::Buckets {
+                next: 0,
+                remaining: self.length,
+            },
+            buckets: &self.buckets,
+        }
+    }
+
+    // An iterator visiting all key-value pairs in ascending order of the keys
+    pub fn sorted_iter(&self) -> std::vec::IntoIter<(&K, &V)>
+    where
+        K: Ord,
+    {
+        let mut entries: Vec<_> = self.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries.into_iter()
+    }
+
+    // An iterator visiting all key-value pairs in arbitrary order, with mutable references to the values,
+    // the iterator element type is `(&'a K, &'a mut V)`
+    #[inline]
+    pub fn iter_mut(&mut self) -> HashMapIterMut<'_, K, V> {
+        HashMapIterMut {
+            inner: self.buckets.iter_mut(),
+        }
+    }
+
+    // Visits the entries in the order of `iter` with mutable values, and stops as soon as `f`
+    // returns false
+    pub fn update_while<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        for &index in &self.occupied {
+            if let Slot::KeyPair(ref k, ref mut v) = self.buckets[index] {
+                if !f(k, v) {
+                    return;
+                }
+            }
+        }
+    }
+
+    // Moves all entries of `other` into the map, a key present in both maps keeps its entry
+    // and `f(key, existing, incoming)` folds the incoming value into the existing one
+    pub fn merge_with<F>(&mut self, other: HashMap<K, V, S>, mut f: F)
+    where
+        F: FnMut(&K, &mut V, V),
+    {
+        for (key, val) in other {
+            let hash = self.hash_of(&key);
+            match self.probe(hash, &key) {
+                Probe::Found(index) => {
+                    if let Slot::KeyPair(ref k, ref mut v) = self.buckets[index] {
+                        f(k, v, val);
+                    }
+                    self.record_insert(index);
+                }
+                Probe::Vacant(vacant) => {
+                    self.insert_new(vacant, hash, key, val);
+                }
+            }
+        }
+    }
+
+    // Consumes the map into a map of the keys mapped by `f`, with the same capacity and settings.
+    // When two keys map to the same key, the entry reinserted last wins, in arbitrary order
+    pub fn map_keys<F>(self, mut f: F) -> HashMap<K, V, S>
+    where
+        F: FnMut(K) -> K,
+    {
+        self.transform(|key, val| Some((f(key), val)))
+    }
+
+    // Consumes the map into a map of the pairs `f` returns, `None` drops the entry. Both key and
+    // value may change, the new map has the same capacity and settings. When two entries end up
+    // with the same key, the one reinserted last wins, in arbitrary order
+    pub fn transform<F>(self, mut f: F) -> HashMap<K, V, S>
+    where
+        F: FnMut(K, V) -> Option<(K, V)>,
+    {
+        let mut map = HashMap::with_capacity_and_hasher(self.capacity, self.hash_builder);
+        map.max_load = self.max_load;
+        map.max_probe = self.max_probe;
+        map.on_resize = self.on_resize;
+        map.auto_shrink = self.auto_shrink;
+        map.compact = self.compact;
+        map.bloom = self.bloom.map(|mut bloom| {
+            bloom.clear();
+            bloom
+        });
+        let pairs = self.buckets.into_iter().filter_map(Slot::into_pair);
+        for (key, val) in pairs.filter_map(|(key, val)| f(key, val)) {
+            let hash = map.hash_of(&key);
+            match map.probe(hash, &key) {
+                Probe::Found(index) => *map.buckets[index].value_mut().unwrap() = val,
+                Probe::Vacant(vacant) => {
+                    map.insert_new(vacant, hash, key, val);
+                }
+            }
+        }
+        map
+    }
+
+    // Consumes the map into a map of the values mapped by `f`. The keys and their hashes don't
+    // change, so every entry stays in its bucket and nothing is rehashed
+    pub fn map_values<W, F>(self, mut f: F) -> HashMap<K, W, S>
+    where
+        F: FnMut(V) -> W,
+    {
+        let buckets = self
+            .buckets
+            .into_iter()
+            .map(|slot| match slot {
+                Slot::KeyPair(k, v) => Slot::KeyPair(k, f(v)),
+                Slot::Deleted => Slot::Deleted,
+                Slot::Empty => Slot::Empty,
+            })
+            .collect();
+        HashMap {
+            buckets,
+            capacity: self.capacity,
+            length: self.length,
+            used: self.used,
+            dirty: self.dirty,
+            occupied: self.occupied,
+            occupied_at: self.occupied_at,
+            psl: self.psl,
+            hashes: self.hashes,
+            max_load: self.max_load,
+            max_probe: self.max_probe,
+            on_resize: self.on_resize,
+            auto_shrink: self.auto_shrink,
+            compact: self.compact,
+            bloom: self.bloom,
+            // the values of the recorded operations have the old type
+            recorder: None,
+            hash_builder: self.hash_builder,
+        }
+    }
+
+    // Compares like `==`, as if the keys of `ignore` were absent from both maps, e.g. volatile
+    // timestamps in a test. Each entry is checked against every ignored key, meant for a few
+    pub fn eq_ignoring<'q, Q, I>(&self, other: &HashMap<K, V, S>, ignore: I) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq + 'q,
+        V: PartialEq,
+        I: IntoIterator<Item = &'q Q>,
+    {
+        let ignore: Vec<&Q> = ignore.into_iter().collect();
+        let kept = |key: &K| !ignore.iter().any(|&q| key.borrow() == q);
+        let mut len = 0;
+        for (key, value) in self.iter().filter(|(k, _)| kept(k)) {
+            if other.get(key.borrow()) != Some(value) {
+                return false;
+            }
+            len += 1;
+        }
+        len == other.keys().filter(|k| kept(k)).count()
+    }
+
+    // Consumes the map into a vector of all key-value pairs in arbitrary order
+    pub fn into_vec(self) -> Vec<(K, V)> {
+        let mut pairs = Vec::with_capacity(self.len());
+        pairs.extend(self);
+        pairs
+    }
+}
+
+impl<V, S> HashMap<String, V, S>
+where
+    S: BuildHasher,
+{
+    // Inserts like `insert_std` with a borrowed key, the `String` is only allocated when the
+    // key is new
+    pub fn insert_str(&mut self, key: &str, val: V) -> Option<V> {
+        match self.entry_ref(key) {
+            EntryRef::Occupied(mut entry) => Some(entry.overwrite(val)),
+            EntryRef::Vacant(entry) => {
+                entry.insert(val);
+                None
+            }
+        }
+    }
 }
 
-impl<'a, K, V> Iterator for Values<'a, K, V> {
-    type Item = &'a V;
+// Bucket bookkeeping which doesn't need to hash the keys
+impl<K, V, S> HashMap<K, V, S> {
+    // The bucket array as it is, including empty buckets and tombstones, e.g. to snapshot the
+    // physical layout. Which bucket holds an entry and which variants show up are implementation
+    // details that may change between versions
+    pub fn raw_slots(&self) -> &[Slot<K, V>] {
+        &self.buckets
+    }
+
+    #[inline]
+    fn is_used(&self, index: usize) -> bool {
+        self.used[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    #[inline]
+    fn is_dirty(&self, index: usize) -> bool {
+        self.dirty[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    #[inline]
+    fn set_dirty(&mut self, index: usize, dirty: bool) {
+        if dirty {
+            self.dirty[index / 64] |= 1 << (index % 64);
+        } else {
+            self.dirty[index / 64] &= !(1 << (index % 64));
+        }
+    }
+
+    // Places a new entry into the free bucket `index`
+    fn occupy(&mut self, index: usize, hash: u64, key: K, val: V) {
+        self.buckets[index] = Slot::KeyPair(key, val);
+        self.hashes[index] = hash;
+        self.used[index / 64] |= 1 << (index % 64);
+        self.occupied_at[index] = self.occupied.len();
+        self.occupied.push(index);
+        self.length += 1;
+    }
+
+    // Removes the entry at position `pos` of the occupied index, the last position takes its
+    // place. A single removal shifts the cluster back, bulk removals leave a tombstone instead so
+    // a cluster isn't shifted once per removed entry. Either way the remaining entries keep their
+    // positions, so a walk over the index which re-examines `pos` visits each of them once
+    fn remove_at(&mut self, pos: usize, backshift: bool) -> (K, V) {
+        let index = self.occupied[pos];
+        if let (Some(log), Slot::KeyPair(ref k, _)) = (self.recorder.as_mut(), &self.buckets[index])
+        {
+            log.remove(k);
+        }
+        if backshift {
+            return self.backshift_remove(index);
+        }
+        let removed = std::mem::replace(&mut self.buckets[index], Slot::Deleted);
+        self.set_dirty(index, false);
+        debug_assert!(self.length > 0, "length underflow in remove");
+        self.unlink_occupied(index);
+        self.length -= 1;
+        removed.into_pair().expect("removed a free bucket")
+    }
+
+    // Removes the entry of the bucket `index` with backward-shift deletion: the following entries
+    // of its cluster move one bucket back, closer to their ideal buckets, up to a free bucket or an
+    // entry in its ideal bucket. So no tombstone is left, unless the cluster ends at a tombstone,
+    // then the hole becomes one to keep the entries behind reachable
+    fn backshift_remove(&mut self, index: usize) -> (K, V) {
+        let removed = std::mem::replace(&mut self.buckets[index], Slot::Empty);
+        debug_assert!(self.length > 0, "length underflow in remove");
+        self.unlink_occupied(index);
+        self.length -= 1;
+
+        let mut hole = index;
+        loop {
+            let next = (hole + 1) % self.buckets.len();
+            let shifts = matches!(self.buckets[next], Slot::KeyPair(..)) && self.psl[next] > 0;
+            if !shifts {
+                // the hole keeps the probe sequence length of the entry which left it
+                if self.buckets[next].is_deleted() && self.psl[next] > 0 {
+                    self.buckets[hole] = Slot::Deleted;
+                } else {
+                    self.used[hole / 64] &= !(1 << (hole % 64));
+                }
+                self.set_dirty(hole, false);
+                break;
+            }
+            self.buckets.swap(hole, next);
+            self.set_dirty(hole, self.is_dirty(next));
+            self.psl[hole] = self.psl[next] - 1;
+            self.hashes[hole] = self.hashes[next];
+            let pos = self.occupied_at[next];
+            self.occupied[pos] = hole;
+            self.occupied_at[hole] = pos;
+            hole = next;
+        }
+        removed.into_pair().expect("removed a free bucket")
+    }
+
+    // Drops the bucket `index` from the occupied index after its entry was removed
+    fn unlink_occupied(&mut self, index: usize) {
+        let pos = self.occupied_at[index];
+        self.occupied.swap_remove(pos);
+        if let Some(&moved) = self.occupied.get(pos) {
+            self.occupied_at[moved] = pos;
+        }
+    }
+}
+
+impl<K, V, S> Default for HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    fn default() -> HashMap<K, V, S> {
+        HashMap::with_hasher(S::default())
+    }
+}
+
+// Inserts every pair with `insert_std`, so the map grows as needed and a later pair of a key
+// replaces the value of an earlier one
+impl<K, V, S> Extend<(K, V)> for HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for (k, v) in iter {
+            self.insert_std(k, v);
+        }
+    }
+}
+
+// Copies the pairs in, e.g. to extend a map with the `iter` of another
+impl<'a, K, V, S> Extend<(&'a K, &'a V)> for HashMap<K, V, S>
+where
+    K: Copy + Hash + Eq,
+    V: Copy,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().map(|(&k, &v)| (k, v)));
+    }
+}
+
+impl<K, V, S> FromIterator<(K, V)> for HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> HashMap<K, V, S> {
+        let mut map = HashMap::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<'a, K, V, S> FromIterator<(&'a K, &'a V)> for HashMap<K, V, S>
+where
+    K: Copy + Hash + Eq,
+    V: Copy,
+    S: BuildHasher + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (&'a K, &'a V)>>(iter: I) -> HashMap<K, V, S> {
+        let mut map = HashMap::default();
+        map.extend(iter);
+        map
+    }
+}
+
+// Moves the entries of a std map over. Its keys are distinct, so they're placed without lookups,
+// into a map sized for them which grows with `DEFAULT_LOAD_FACTOR`
+impl<K, V, T> From<std::collections::HashMap<K, V, T>> for HashMap<K, V>
+where
+    K: Hash + Eq,
+{
+    fn from(std_map: std::collections::HashMap<K, V, T>) -> HashMap<K, V> {
+        let mut map = HashMap::with_capacity(HashMap::<K, V>::capacity_for(std_map.len()));
+        map.max_load = Some(DEFAULT_LOAD_FACTOR);
+        for (key, val) in std_map {
+            let hash = map.hash_of(&key);
+            let home = map.home_bucket(hash);
+            map.insert_new(Some((home, 0)), hash, key, val);
+        }
+        map
+    }
+}
+
+// Moves the entries into a std map, e.g. for libraries which require one
+impl<K, V, S> From<HashMap<K, V, S>> for std::collections::HashMap<K, V>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    fn from(map: HashMap<K, V, S>) -> std::collections::HashMap<K, V> {
+        let mut std_map = std::collections::HashMap::with_capacity(map.len());
+        std_map.extend(map);
+        std_map
+    }
+}
+
+impl<K, V, S> PartialEq for HashMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    S: BuildHasher,
+{
+    // Compares the live entries only, so capacities and tombstones don't matter. Takes one
+    // lookup in `other` per entry of `self`, O(len) on average. Equal lengths plus every entry
+    // of `self` found in `other` means both hold the same entries, as `len` counts live entries
+    fn eq(&self, other: &HashMap<K, V, S>) -> bool {
+        if self.len() != other.len() {
+            return false;
+        }
+
+        self.iter()
+            .all(|(key, value)| other.get(key).is_some_and(|v| *value == *v))
+    }
+}
+
+impl<K, V, S> Eq for HashMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: Eq,
+    S: BuildHasher,
+{
+}
+
+// Formats the entries as `{k1=v1, k2=v2}` in iteration order, an empty map is `{}`
+impl<K, V, S> fmt::Display for HashMap<K, V, S>
+where
+    K: Eq + Hash + fmt::Display,
+    V: fmt::Display,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("{")?;
+        for (i, (k, v)) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}={}", k, v)?;
+        }
+        f.write_str("}")
+    }
+}
+
+// The most buckets a map may allocate, neither the bucket array nor the arrays alongside it
+// (`occupied`, `occupied_at`, `psl` and `hashes`) can exceed `isize::MAX` bytes
+fn max_capacity<K, V>() -> usize {
+    let per_bucket = std::mem::size_of::<Slot<K, V>>() + 3 * std::mem::size_of::<usize>() + 8;
+    isize::MAX as usize / per_bucket
+}
+
+// Doubles the capacity for growing, panics rather than overflowing past `limit`
+fn double_capacity(capacity: usize, limit: usize) -> usize {
+    match capacity.checked_mul(2) {
+        Some(doubled) if doubled <= limit => doubled,
+        _ => panic!("capacity overflow: can't grow beyond {} buckets", capacity),
+    }
+}
+
+// Folds the high half of the hash into the low half before it's reduced to a bucket index, so
+// a 32-bit `usize` doesn't drop half of the hash
+#[inline]
+fn fold_hash(hash: u64) -> usize {
+    (hash ^ (hash >> 32)) as usize
+}
+
+// Where the probe sequence of a key ended
+enum Probe {
+    // the key lives in this bucket
+    Found(usize),
+    // the key is absent, placing it starts at `(bucket, psl)`, `None` when no bucket within
+    // the probe limit can take it
+    Vacant(Option<(usize, usize)>),
+}
+
+// How far the entries are displaced from their ideal buckets, returned by `probe_stats`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ProbeStats {
+    // The longest distance of an entry from its ideal bucket
+    pub max_displacement: usize,
+    // The sum of all entries' distances from their ideal buckets
+    pub total_displacement: usize,
+}
+
+// How `insert_all` placed its pairs
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InsertStats {
+    // The keys which were absent before
+    pub inserted: usize,
+    // The pairs which overwrote the value of a present key
+    pub updated: usize,
+}
+
+// The error of `try_get`, holds the key which wasn't found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyNotFound<'q, Q: ?Sized> {
+    key: &'q Q,
+}
+
+impl<'q, Q: ?Sized> KeyNotFound<'q, Q> {
+    pub fn key(&self) -> &'q Q {
+        self.key
+    }
+}
+
+impl<Q: ?Sized + fmt::Display> fmt::Display for KeyNotFound<'_, Q> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "key not found: {}", self.key)
+    }
+}
+
+impl<Q: ?Sized + fmt::Debug + fmt::Display> std::error::Error for KeyNotFound<'_, Q> {}
+
+// The error of `from_iter_unique`, holds the key which occurred twice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DuplicateKey<K>(pub K);
+
+impl<K: fmt::Display> fmt::Display for DuplicateKey<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "duplicate key: {}", self.0)
+    }
+}
+
+impl<K: fmt::Debug + fmt::Display> std::error::Error for DuplicateKey<K> {}
+
+// The error of `get_many_mut`, holds the positions of the offending keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GetManyError {
+    // `keys[i]` isn't in the map
+    Missing(usize),
+    // `keys[i]` and `keys[j]` are the same key
+    Duplicate(usize, usize),
+}
+
+impl fmt::Display for GetManyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            GetManyError::Missing(i) => write!(f, "key {} not found", i),
+            GetManyError::Duplicate(i, j) => write!(f, "keys {} and {} are the same", i, j),
+        }
+    }
+}
+
+impl std::error::Error for GetManyError {}
+
+// A snapshot of the map for monitoring, returned by `stats`. The probe length of an entry is
+// the number of buckets a lookup of it examines, one for an entry in its ideal bucket
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct HashMapStats {
+    pub capacity: usize,
+    pub len: usize,
+    pub load_factor: f64,
+    pub tombstones: usize,
+    pub max_probe_length: usize,
+    pub avg_probe_length: f64,
+}
+
+pub struct HashMapIter<'a, K: 'a, V: 'a> {
+    inner: Walk<'a>,
+    buckets: &'a [Slot<K, V>],
+}
+
+#[derive(Clone)]
+enum Walk<'a> {
+    // the occupied index of `iter`, so empty and deleted buckets are never visited
+    Occupied(Iter<'a, usize>),
+    // the buckets of `iter_from` in order, `remaining` entries live at `next` or after it
+    Buckets { next: usize, remaining: usize },
+}
+
+impl<K, V> HashMapIter<'_, K, V> {
+    // The bucket an iterator from `iter_from` examines next, pass it to `iter_from` to resume
+    // the scan. `None` for `iter`, which doesn't visit the buckets in order
+    pub fn next_bucket(&self) -> Option<usize> {
+        match self.inner {
+            Walk::Occupied(_) => None,
+            Walk::Buckets { next, .. } => Some(next),
+        }
+    }
+
+    #[inline]
+    fn remaining(&self) -> usize {
+        match self.inner {
+            Walk::Occupied(ref indices) => indices.len(),
+            Walk::Buckets { remaining, .. } => remaining,
+        }
+    }
+}
+
+// Implemented by hand, deriving would require `K: Clone` and `V: Clone`
+impl<K, V> Clone for HashMapIter<'_, K, V> {
+    fn clone(&self) -> Self {
+        HashMapIter {
+            inner: self.inner.clone(),
+            buckets: self.buckets,
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for HashMapIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let buckets = self.buckets;
+        match self.inner {
+            Walk::Occupied(ref mut indices) => Some(live_pair(&buckets[*indices.next()?])),
+            Walk::Buckets {
+                ref mut next,
+                ref mut remaining,
+            } => {
+                while let Some(slot) = buckets.get(*next) {
+                    *next += 1;
+                    if let Some(pair) = slot.pair() {
+                        *remaining -= 1;
+                        return Some(pair);
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining(), Some(self.remaining()))
+    }
+
+    // The number of remaining entries is known, so neither needs to walk the buckets
+    #[inline]
+    fn count(self) -> usize {
+        self.remaining()
+    }
+
+    #[inline]
+    fn last(self) -> Option<(&'a K, &'a V)> {
+        match self.inner {
+            Walk::Occupied(indices) => Some(live_pair(&self.buckets[*indices.last()?])),
+            Walk::Buckets { next, .. } => self.buckets[next..].iter().rev().find_map(Slot::pair),
+        }
+    }
+
+    // Walks the occupied index in one tight loop, which speeds up `sum`, `count`, `for_each` etc.
+    // `try_fold` can't be overridden on stable Rust, it falls back to `next`
+    fn fold<B, F>(self, init: B, mut f: F) -> B
+    where
+        F: FnMut(B, Self::Item) -> B,
+    {
+        let buckets = self.buckets;
+        match self.inner {
+            Walk::Occupied(indices) => {
+                indices.fold(init, |acc, &index| f(acc, live_pair(&buckets[index])))
+            }
+            Walk::Buckets { next, .. } => {
+                buckets[next..].iter().filter_map(Slot::pair).fold(init, f)
+            }
+        }
+    }
+}
+
+impl<K, V> ExactSizeIterator for HashMapIter<'_, K, V> {}
+
+// The entry of a bucket from the occupied index
+#[inline]
+fn live_pair<K, V>(slot: &Slot<K, V>) -> (&K, &V) {
+    match *slot {
+        Slot::KeyPair(ref key, ref val) => (key, val),
+        _ => unreachable!("occupied index points to a free bucket"),
+    }
+}
+
+pub struct HashMapIterMut<'a, K: 'a, V: 'a> {
+    inner: IterMut<'a, Slot<K, V>>,
+}
+
+impl<'a, K, V> Iterator for HashMapIterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
+        let mut n = self.inner.next();
+        loop {
+            match n {
+                Some(&mut Slot::KeyPair(ref key, ref mut val)) => return Some((key, val)),
+                // skip both empty and deleted buckets
+                Some(..) => {
+                    n = self.inner.next();
+                }
+                None => {
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+impl<K, V, S> IntoIterator for HashMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    // Consumes the map into an iterator visiting all key-value pairs in arbitrary order
+    #[inline]
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter {
+            inner: self.buckets.into_iter(),
+        }
+    }
+}
+
+pub struct IntoIter<K, V> {
+    inner: std::vec::IntoIter<Slot<K, V>>,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    #[inline]
+    fn next(&mut self) -> Option<(K, V)> {
+        self.inner.by_ref().find_map(Slot::into_pair)
+    }
+}
+
+pub struct DrainFilter<'a, K: 'a, V: 'a, F, S = SeededState>
+where
+    K: Hash + Eq,
+    F: FnMut(&K, &mut V) -> bool,
+    S: BuildHasher,
+{
+    map: &'a mut HashMap<K, V, S>,
+    pred: F,
+    // the position in the occupied index of the next entry to examine
+    pos: usize,
+}
+
+impl<K, V, F, S> Iterator for DrainFilter<'_, K, V, F, S>
+where
+    K: Hash + Eq,
+    F: FnMut(&K, &mut V) -> bool,
+    S: BuildHasher,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        while self.pos < self.map.occupied.len() {
+            let matched = match self.map.buckets[self.map.occupied[self.pos]] {
+                Slot::KeyPair(ref k, ref mut v) => (self.pred)(k, v),
+                _ => unreachable!("the occupied index points at a free bucket"),
+            };
+            if matched {
+                return Some(self.map.remove_at(self.pos, false));
+            }
+            self.pos += 1;
+        }
+        None
+    }
+}
+
+impl<K, V, F, S> Drop for DrainFilter<'_, K, V, F, S>
+where
+    K: Hash + Eq,
+    F: FnMut(&K, &mut V) -> bool,
+    S: BuildHasher,
+{
+    // Shrinking moves the entries, so it waits until the walk over the occupied index is done
+    fn drop(&mut self) {
+        self.for_each(drop);
+        self.map.shrink_if_sparse();
+    }
+}
+
+pub struct Keys<'a, K: 'a, V: 'a> {
+    inner: HashMapIter<'a, K, V>,
+}
+
+impl<K, V> Clone for Keys<'_, K, V> {
+    fn clone(&self) -> Self {
+        Keys {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a K> {
+        self.inner.next().map(|(k, _)| k)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    #[inline]
+    fn count(self) -> usize {
+        self.inner.count()
+    }
+
+    #[inline]
+    fn last(self) -> Option<&'a K> {
+        self.inner.last().map(|(k, _)| k)
+    }
+
+    #[inline]
+    fn fold<B, F>(self, init: B, mut f: F) -> B
+    where
+        F: FnMut(B, Self::Item) -> B,
+    {
+        self.inner.fold(init, |acc, (k, _)| f(acc, k))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Keys<'_, K, V> {}
+
+pub struct Values<'a, K: 'a, V: 'a> {
+    inner: HashMapIter<'a, K, V>,
+}
+
+impl<K, V> Clone for Values<'_, K, V> {
+    fn clone(&self) -> Self {
+        Values {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a V> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    #[inline]
+    fn count(self) -> usize {
+        self.inner.count()
+    }
+
+    #[inline]
+    fn last(self) -> Option<&'a V> {
+        self.inner.last().map(|(_, v)| v)
+    }
+
+    #[inline]
+    fn fold<B, F>(self, init: B, mut f: F) -> B
+    where
+        F: FnMut(B, Self::Item) -> B,
+    {
+        self.inner.fold(init, |acc, (_, v)| f(acc, v))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Values<'_, K, V> {}
+
+pub enum Slot<K, V> {
+    Empty,
+    // A tombstone left by `retain` or `drain_filter`, `remove` shifts the following entries
+    // back instead
+    Deleted,
+    KeyPair(K, V),
+}
+
+impl<K, V> Slot<K, V> {
+    #[inline]
+    pub fn key(&self) -> Option<&K> {
+        match *self {
+            Slot::KeyPair(ref k, _) => Some(k),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn value(&self) -> Option<&V> {
+        match *self {
+            Slot::KeyPair(_, ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn value_mut(&mut self) -> Option<&mut V> {
+        match *self {
+            Slot::KeyPair(_, ref mut v) => Some(v),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        matches!(*self, Slot::Empty)
+    }
+
+    #[inline]
+    pub fn is_deleted(&self) -> bool {
+        matches!(*self, Slot::Deleted)
+    }
+
+    #[inline]
+    fn pair(&self) -> Option<(&K, &V)> {
+        match *self {
+            Slot::KeyPair(ref k, ref v) => Some((k, v)),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn pair_mut(&mut self) -> Option<(&K, &mut V)> {
+        match *self {
+            Slot::KeyPair(ref k, ref mut v) => Some((k, v)),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn into_pair(self) -> Option<(K, V)> {
+        match self {
+            Slot::KeyPair(k, v) => Some((k, v)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{
+        double_capacity, fold_hash, max_capacity, DuplicateKey, Entry, GetManyError, HashMap,
+        SeededState, Slot, DEFAULT_LOAD_FACTOR,
+    };
+
+    // Finds `n` keys sharing the same ideal bucket in `m`
+    fn colliding_keys<V>(m: &HashMap<u64, V>, n: usize) -> Vec<u64> {
+        keys_with_home(m, m.find_bucket(&0u64), n)
+    }
+
+    // Finds `n` keys whose ideal bucket in `m` is `home`
+    fn keys_with_home<V>(m: &HashMap<u64, V>, home: usize, n: usize) -> Vec<u64> {
+        (0u64..)
+            .filter(|k| m.find_bucket(k) == home)
+            .take(n)
+            .collect()
+    }
+
+    #[test]
+    fn default_new() {
+        let m: HashMap<usize, usize> = HashMap::new();
+        assert_eq!(m.len(), 0);
+        assert_eq!(m.cap(), 16);
+        assert_eq!(m.cap(), HashMap::<usize, usize>::DEFAULT_CAPACITY);
+        assert_eq!(m.get(&1), None);
+
+        let mut count = 0;
+        for (_, _) in m.iter() {
+            count += 1;
+        }
+        assert_eq!(count, 0);
+
+        // the default capacity is no limit, `new` and `default` maps grow
+        let mut m = HashMap::new();
+        let mut d: HashMap<usize, usize> = HashMap::default();
+        for i in 0..100 {
+            assert_eq!(m.insert(i, i), (true, None));
+            assert_eq!(d.insert(i, i), (true, None));
+        }
+        assert_eq!(m.len(), 100);
+        assert_eq!(d.len(), 100);
+        assert!(m.cap() > 100);
+        assert!((0..100).all(|i| m.get(&i) == Some(&i) && d.get(&i) == Some(&i)));
+    }
+
+    #[test]
+    #[allow(clippy::bool_assert_comparison, clippy::single_match)]
+    fn test_common() {
+        let mut m = HashMap::with_capacity(3);
+        assert_eq!(m.cap(), 3);
+        assert_eq!(m.len(), 0);
+
+        // insert key 1, get result
+        assert_eq!(m.insert(1, 100), (true, None));
+        match m.get(&1) {
+            Some(v) => assert_eq!(*v, 100),
+            None => panic!("panicure!"),
+        }
+
+        // insert more keys
+        assert_eq!(m.insert(2, 200), (true, None));
+        assert_eq!(m.insert(3, 300), (true, None));
+        assert_eq!(m.len(), 3);
+
+        // our hashmap capacity is 3, now it's filled, can't be insert anymore
+        assert_eq!(m.insert(4, 400), (false, None));
+
+        // although it's filled, but we can update the existing key, return true
+        assert_eq!(m.insert(1, 1000), (true, Some(100)));
+        // assert the new value
+        match m.get(&1) {
+            Some(v) => assert_eq!(*v, 1000),
+            None => panic!("panicure!"),
+        }
+
+        // remove key 1, vacated a bucket
+        assert_eq!(m.remove(&1), true);
+        match m.get(&1) {
+            Some(y) => panic!("key {} must not exists!", y),
+            None => {}
+        }
+        assert_eq!(m.contains_key(&1), false);
+        assert_eq!(m.len(), 2);
+        for (&k, &v) in m.iter() {
+            match k {
+                2 => assert_eq!(v, 200),
+                3 => assert_eq!(v, 300),
+                _ => {}
+            }
+        }
+
+        // we can insert a key/value now
+        assert_eq!(m.insert(4, 400), (true, None));
+        assert_eq!(m.len(), 3);
+        for (&k, &v) in m.iter() {
+            match k {
+                2 => assert_eq!(v, 200),
+                3 => assert_eq!(v, 300),
+                4 => assert_eq!(v, 400),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let mut m = HashMap::new();
+        m.insert("foo", 42);
+        m.insert("bar", 43);
+        match m.get("foo") {
+            Some(v) => assert_eq!(*v, 42),
+            None => panic!("panicure!"),
+        }
+
+        // change value of key `foo`
+        match m.get_mut("foo") {
+            Some(v) => *v = 40,
+            None => panic!("panicure!"),
+        }
+
+        // get the changed value
+        match m.get("foo") {
+            Some(v) => assert_eq!(*v, 40),
+            None => panic!("panicure!"),
+        }
+    }
+
+    #[test]
+    fn test_iter_mut() {
+        let mut m = HashMap::new();
+        m.insert("foo", 42);
+        m.insert("bar", 43);
+        for (_, v) in m.iter_mut() {
+            *v += 1;
+        }
+
+        for (&k, &v) in m.iter() {
+            match k {
+                "foo" => assert_eq!(v, 43),
+                "bar" => assert_eq!(v, 44),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_keys_values() {
+        let mut map = HashMap::new();
+        map.insert("foo", 42);
+        map.insert("bar", 43);
+
+        for key in map.keys() {
+            println!("{}", key);
+        }
+
+        for val in map.values() {
+            println!("{}", val);
+        }
+    }
+
+    #[test]
+    #[allow(clippy::bool_assert_comparison)]
+    fn test_eq() {
+        let mut map1 = HashMap::new();
+        map1.insert("foo", 42);
+        map1.insert("bar", 43);
+
+        let mut map2 = HashMap::new();
+        map2.insert("foo", 42);
+        map2.insert("bar", 43);
+
+        let eq = map1 == map2;
+        assert_eq!(true, eq)
+    }
+
+    #[test]
+    fn test_try_insert_bounded() {
+        let mut m = HashMap::with_capacity(3);
+        assert_eq!(m.try_insert_bounded(1, 100), Ok(None));
+        assert_eq!(m.try_insert_bounded(2, 200), Ok(None));
+        assert_eq!(m.try_insert_bounded(3, 300), Ok(None));
+
+        // the map is filled, the overflowing pair is given back untouched
+        assert_eq!(m.try_insert_bounded(4, 400), Err((4, 400)));
+        assert_eq!(m.len(), 3);
+        assert_eq!(m.cap(), 3);
+
+        // updating an existing key is still allowed
+        assert_eq!(m.try_insert_bounded(1, 1000), Ok(Some(100)));
+        assert_eq!(m.get(&1), Some(&1000));
+    }
+
+    #[test]
+    fn test_get_many() {
+        let mut m = HashMap::new();
+        m.insert("foo", 42);
+        m.insert("bar", 43);
+
+        let vals = m.get_many(&["bar", "baz", "foo", "bar"]);
+        assert_eq!(vals.len(), 4);
+        assert_eq!(vals[0], Some(&43));
+        assert_eq!(vals[1], None);
+        assert_eq!(vals[2], Some(&42));
+        assert_eq!(vals[3], Some(&43));
+
+        assert!(m.get_many::<str>(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_retain_count() {
+        let mut m = HashMap::new();
+        for i in 1..=10 {
+            m.insert(i, i * 10);
+        }
+
+        let removed = m.retain_count(|k, _| k % 2 == 0);
+        assert_eq!(removed, 5);
+        assert_eq!(m.len(), 5);
+        let mut keys: Vec<_> = m.keys().cloned().collect();
+        keys.sort();
+        assert_eq!(keys, vec![2, 4, 6, 8, 10]);
+
+        // nothing left to remove
+        m.retain(|_, v| {
+            *v += 1;
+            true
+        });
+        assert_eq!(m.len(), 5);
+        assert!(m.values().all(|v| v % 10 == 1));
+    }
+
+    #[test]
+    fn test_with_load_factor() {
+        let mut m = HashMap::with_load_factor(0.75);
+        assert_eq!(m.cap(), 16);
+
+        // fill up to the load factor, no resize occurs
+        for i in 0..12 {
+            assert_eq!(m.insert(i, i), (true, None));
+        }
+        assert_eq!(m.cap(), 16);
+        assert_eq!(m.load_factor(), 0.75);
+
+        // updating an existing key doesn't grow the map
+        assert_eq!(m.insert(0, 1), (true, Some(0)));
+        assert_eq!(m.cap(), 16);
+
+        // crossing the load factor grows the map
+        assert_eq!(m.insert(12, 12), (true, None));
+        assert_eq!(m.cap(), 32);
+        assert_eq!(m.len(), 13);
+        for i in 1..=12 {
+            assert_eq!(m.get(&i), Some(&i));
+        }
+
+        // a denser map: 14 of 16 buckets stay below 0.9, the 15th entry crosses it
+        let mut dense = HashMap::with_load_factor(0.9);
+        for i in 0..14 {
+            assert_eq!(dense.insert(i, i), (true, None));
+        }
+        assert_eq!(dense.cap(), 16);
+        assert!(dense.load_factor() < 0.9);
+        assert_eq!(dense.insert(14, 14), (true, None));
+        assert_eq!(dense.cap(), 32);
+        assert_eq!(dense.len(), 15);
+
+        // the factor is clamped
+        let m: HashMap<usize, usize> = HashMap::with_load_factor(2.0);
+        assert_eq!(m.max_load, Some(0.95));
+    }
+
+    #[test]
+    fn test_reserve() {
+        let mut m = HashMap::with_capacity(4);
+        m.insert(1, 1);
+        m.reserve(3);
+        assert_eq!(m.cap(), 4);
+        m.reserve(4);
+        assert_eq!(m.cap(), 8);
+        assert_eq!(m.get(&1), Some(&1));
+    }
+
+    #[test]
+    fn test_iter_skips_tombstones() {
+        let mut m = HashMap::with_capacity(3);
+        m.insert(1, 10);
+        m.insert(2, 20);
+        m.insert(3, 30);
+        let middle = *m.keys().nth(1).unwrap();
+        m.retain(|&k, _| k != middle);
+        assert_eq!(m.buckets.iter().filter(|s| s.is_deleted()).count(), 1);
+
+        let mut visited = Vec::new();
+        for (&k, v) in m.iter_mut() {
+            *v += 1;
+            visited.push(k);
+        }
+        visited.sort();
+        let expected: Vec<_> = [1, 2, 3].into_iter().filter(|&k| k != middle).collect();
+        assert_eq!(visited, expected);
+
+        assert_eq!(m.iter().count(), 2);
+        assert_eq!(m.keys().count(), 2);
+        assert_eq!(m.values().count(), 2);
+        for k in expected {
+            assert_eq!(m.get(&k), Some(&(k * 10 + 1)));
+        }
+        assert_eq!(m.get(&middle), None);
+
+        // the tombstone is reused by the next insert
+        assert_eq!(m.insert(middle, 0), (true, None));
+        assert_eq!(m.len(), 3);
+        assert!(!m.buckets.iter().any(|s| s.is_deleted()));
+    }
+
+    #[test]
+    fn test_display() {
+        let mut map = HashMap::new();
+        assert_eq!(format!("{}", map), "{}");
+
+        map.insert("foo", 42);
+        assert_eq!(format!("{}", map), "{foo=42}");
+
+        map.insert("bar", 43);
+        let s = format!("{}", map);
+        assert!(s.starts_with('{') && s.ends_with('}'));
+        assert!(s.contains("foo=42"));
+        assert!(s.contains("bar=43"));
+        assert!(s.contains(", "));
+    }
+
+    #[test]
+    fn test_sorted_iter() {
+        let mut m = HashMap::new();
+        for k in [5, 3, 9, 1, 7] {
+            m.insert(k, k * 10);
+        }
+        m.remove(&9);
+
+        let sorted: Vec<_> = m.sorted_iter().collect();
+        assert_eq!(sorted, vec![(&1, &10), (&3, &30), (&5, &50), (&7, &70)]);
+    }
+
+    #[test]
+    fn test_rehash() {
+        let mut m = HashMap::with_capacity(16);
+        let keys = colliding_keys(&m, 6);
+        for &k in &keys {
+            m.insert(k, k);
+        }
+        assert_eq!(m.probe_stats().max_displacement, 5);
+
+        // remove the head of the cluster, the survivors keep their displacement
+        m.retain(|k, _| !keys[..3].contains(k));
+        let before = m.probe_stats();
+        assert_eq!(before.max_displacement, 5);
+        assert_eq!(m.buckets.iter().filter(|s| s.is_deleted()).count(), 3);
+
+        m.rehash();
+        let after = m.probe_stats();
+        assert_eq!(after.max_displacement, 2);
+        assert!(after.total_displacement < before.total_displacement);
+        assert!(!m.buckets.iter().any(|s| s.is_deleted()));
+        assert_eq!(m.cap(), 16);
+        assert_eq!(m.len(), 3);
+        for k in &keys[3..] {
+            assert_eq!(m.get(k), Some(k));
+        }
+    }
+
+    #[test]
+    fn test_max_probe() {
+        let mut m = HashMap::with_load_factor(0.9);
+        m.set_max_probe(2);
+        let keys = colliding_keys(&m, 4);
+        for &k in &keys {
+            m.insert(k, k);
+        }
+        // the cluster would need 4 buckets in a row, the map grows instead
+        assert!(m.cap() > 16);
+        assert!(m.probe_stats().max_displacement < 2);
+        for k in &keys {
+            assert_eq!(m.get(k), Some(k));
+        }
+
+        // a fixed capacity map refuses the insert instead of growing
+        let mut m = HashMap::with_capacity(16);
+        m.set_max_probe(2);
+        let keys = colliding_keys(&m, 3);
+        assert_eq!(m.insert(keys[0], 0), (true, None));
+        assert_eq!(m.insert(keys[1], 1), (true, None));
+        assert_eq!(m.insert(keys[2], 2), (false, None));
+        assert_eq!(m.try_insert_bounded(keys[2], 2), Err((keys[2], 2)));
+        assert_eq!(m.cap(), 16);
+        assert_eq!(m.get(&keys[2]), None);
+        assert_eq!(m.get(&keys[1]), Some(&1));
+    }
+
+    #[test]
+    fn test_rehash_full_map() {
+        let mut m = HashMap::with_capacity(3);
+        m.insert(1, 1);
+        m.insert(2, 2);
+        m.insert(3, 3);
+        m.rehash();
+        assert_eq!(m.len(), 3);
+        assert_eq!(m.cap(), 3);
+        for i in 1..=3 {
+            assert_eq!(m.get(&i), Some(&i));
+        }
+    }
+
+    #[test]
+    fn test_resize_hook() {
+        use std::sync::{Arc, Mutex};
+
+        let observed = Arc::new(Mutex::new(Vec::new()));
+        let mut m = HashMap::with_load_factor(0.5);
+        let hook_observed = Arc::clone(&observed);
+        m.set_resize_hook(Box::new(move |old, new| {
+            hook_observed.lock().unwrap().push((old, new))
+        }));
+
+        for i in 0..20 {
+            m.insert(i, i);
+        }
+        // rebuilding at the same capacity isn't a resize
+        m.rehash();
+
+        let observed = observed.lock().unwrap();
+        assert_eq!(*observed, vec![(16, 32), (32, 64)]);
+        assert!(observed.iter().all(|&(old, new)| new > old));
+        assert_eq!(m.cap(), 64);
+    }
+
+    #[test]
+    fn test_into_vec() {
+        let mut m = HashMap::new();
+        for i in 0..10 {
+            m.insert(i, i * 2);
+        }
+        m.remove(&3);
+
+        let mut pairs = m.into_vec();
+        assert_eq!(pairs.len(), 9);
+        pairs.sort();
+        let expected: Vec<_> = (0..10).filter(|&i| i != 3).map(|i| (i, i * 2)).collect();
+        assert_eq!(pairs, expected);
+    }
+
+    #[test]
+    fn test_used_bitmap() {
+        let mut m = HashMap::with_capacity(130);
+        for i in 0..100 {
+            m.insert(i, i);
+        }
+        for i in (0..100).step_by(3) {
+            m.remove(&i);
+        }
+        for i in 100..120 {
+            m.insert(i, i);
+        }
+        for i in 120..1000 {
+            assert_eq!(m.get(&i), None);
+            assert!(!m.contains_key(&i));
+        }
+        for (index, slot) in m.buckets.iter().enumerate() {
+            assert_eq!(m.is_used(index), !slot.is_empty());
+        }
+
+        m.rehash();
+        for (index, slot) in m.buckets.iter().enumerate() {
+            assert_eq!(m.is_used(index), !slot.is_empty());
+        }
+        assert_eq!(m.len(), 86);
+        assert_eq!(m.get(&1), Some(&1));
+        assert_eq!(m.get(&3), None);
+    }
+
+    #[test]
+    fn test_merge_with() {
+        let mut counts = HashMap::new();
+        counts.insert("foo", 1);
+        counts.insert("bar", 2);
+
+        let mut other = HashMap::new();
+        other.insert("bar", 3);
+        other.insert("baz", 4);
+
+        counts.merge_with(other, |_, existing, incoming| *existing += incoming);
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts.get("foo"), Some(&1));
+        assert_eq!(counts.get("bar"), Some(&5));
+        assert_eq!(counts.get("baz"), Some(&4));
+    }
+
+    #[test]
+    fn test_large_capacity() {
+        let mut m: HashMap<usize, usize> = HashMap::with_capacity(1 << 20);
+        assert_eq!(m.cap(), 1 << 20);
+        assert_eq!(m.len(), 0);
+        assert!(m.is_empty());
+        assert_eq!(m.iter().count(), 0);
+        assert_eq!(m.get(&1), None);
+
+        m.insert(1, 1);
+        assert_eq!(m.get(&1), Some(&1));
+    }
+
+    #[test]
+    fn test_sparse_iter() {
+        let mut m = HashMap::with_capacity(1_000_000);
+        for i in 0..10 {
+            m.insert(i, i);
+        }
+        m.remove(&4);
+        m.retain(|k, _| *k != 7);
+        // the freed buckets are reused by new entries
+        m.insert(4, 40);
+        m.insert(10, 100);
+
+        assert_eq!(m.occupied.len(), m.len());
+        let mut entries: Vec<_> = m.iter().map(|(&k, &v)| (k, v)).collect();
+        entries.sort();
+        let mut expected: Vec<_> = (0..10)
+            .filter(|&i| i != 4 && i != 7)
+            .map(|i| (i, i))
+            .collect();
+        expected.push((4, 40));
+        expected.push((10, 100));
+        expected.sort();
+        assert_eq!(entries, expected);
+        assert_eq!(m.keys().count(), 10);
+        assert_eq!(m.values().count(), 10);
+
+        // the index survives a resize
+        m.reserve(2_000_000);
+        let mut resized: Vec<_> = m.iter().map(|(&k, &v)| (k, v)).collect();
+        resized.sort();
+        assert_eq!(resized, expected);
+
+        m.clear();
+        assert_eq!(m.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_drain_filter() {
+        let mut m = HashMap::new();
+        for i in 0..10 {
+            m.insert(i, i * 10);
+        }
+
+        let mut drained: Vec<_> = m.drain_filter(|k, _| k % 2 == 0).collect();
+        drained.sort();
+        assert_eq!(drained, vec![(0, 0), (2, 20), (4, 40), (6, 60), (8, 80)]);
+        assert_eq!(m.len(), 5);
+        for i in 0..10 {
+            assert_eq!(
+                m.get(&i).copied(),
+                if i % 2 == 1 { Some(i * 10) } else { None }
+            );
+        }
+
+        // dropping the iterator early finishes the removal
+        let mut iter = m.drain_filter(|k, _| *k > 4);
+        assert!(iter.next().is_some());
+        drop(iter);
+        assert_eq!(m.len(), 2);
+        assert!(m.contains_key(&1));
+        assert!(m.contains_key(&3));
+    }
+
+    #[test]
+    fn test_clear_and_reset() {
+        let mut m = HashMap::with_load_factor(0.5);
+        for i in 0..200 {
+            m.insert(i, i);
+        }
+        assert_eq!(m.cap(), 512);
+
+        m.clear();
+        assert_eq!(m.len(), 0);
+        assert!(m.is_empty());
+        assert_eq!(m.cap(), 512);
+        assert_eq!(m.get(&1), None);
+
+        m.insert(1, 1);
+        m.reset();
+        assert_eq!(m.len(), 0);
+        assert_eq!(m.cap(), 16);
+        assert_eq!(m.get(&1), None);
+
+        // still grows with the configured load factor
+        for i in 0..9 {
+            m.insert(i, i);
+        }
+        assert_eq!(m.cap(), 32);
+    }
+
+    #[test]
+    fn test_clone_iter() {
+        let mut m = HashMap::new();
+        for i in 0..5 {
+            m.insert(i, i * 10);
+        }
+
+        let keys = m.keys();
+        assert_eq!(keys.clone().count(), 5);
+        let first: Vec<_> = keys.clone().collect();
+        let second: Vec<_> = keys.collect();
+        assert_eq!(first, second);
+
+        let mut values = m.values();
+        values.next();
+        assert_eq!(values.clone().count(), 4);
+        assert_eq!(values.count(), 4);
+
+        let iter = m.iter();
+        assert!(iter.clone().eq(iter));
+    }
+
+    #[test]
+    fn test_fold() {
+        let mut m = HashMap::with_capacity(128);
+        for i in 0..100 {
+            m.insert(i, i * 2);
+        }
+        for i in (0..100).step_by(4) {
+            m.remove(&i);
+        }
+
+        let mut naive = 0;
+        for slot in m.buckets.iter() {
+            if let Some(v) = slot.value() {
+                naive += v;
+            }
+        }
+        let folded = m.values().fold((0, 0), |(sum, n), &v| (sum + v, n + 1));
+        assert_eq!(folded, (naive, 75));
+        assert_eq!(m.values().sum::<i32>(), naive);
+        assert_eq!(m.keys().fold(0, |a, &k| a + k * 2), naive);
+        assert_eq!(m.iter().count(), 75);
+        assert_eq!(m.keys().count(), 75);
+
+        // folding a partially consumed iterator continues where `next` stopped
+        let mut iter = m.iter();
+        iter.next();
+        assert_eq!(iter.fold(0, |n, _| n + 1), 74);
+    }
+
+    #[test]
+    fn test_robin_hood() {
+        let mut m = HashMap::with_capacity(64);
+        // a cluster at bucket 7 is placed first, then a cluster at bucket 0 runs into it
+        let mut keys = keys_with_home(&m, 7, 8);
+        keys.extend(keys_with_home(&m, 0, 8));
+
+        // with plain linear probing every key takes the first free bucket
+        let mut linear = [false; 64];
+        let mut linear_max = 0;
+        for k in &keys {
+            let home = m.find_bucket(k);
+            let mut index = home;
+            while linear[index] {
+                index = (index + 1) % 64;
+            }
+            linear[index] = true;
+            linear_max = linear_max.max(index - home);
+        }
+        assert_eq!(linear_max, 15);
+
+        for &k in &keys {
+            m.insert(k, k);
+        }
+        assert_eq!(m.probe_stats().max_displacement, 8);
+        for k in &keys {
+            assert_eq!(m.get(k), Some(k));
+        }
+
+        // absent keys of both clusters stop early and miss
+        for k in keys_with_home(&m, 0, 10).iter().skip(8) {
+            assert_eq!(m.get(k), None);
+        }
+
+        // the entries after a removed key shift back, the chains stay intact
+        m.remove(&keys[8]);
+        for k in keys.iter().filter(|&&k| k != keys[8]) {
+            assert_eq!(m.get(k), Some(k));
+        }
+        m.insert(keys[8], 0);
+        for k in &keys {
+            assert!(m.contains_key(k));
+        }
+        assert_eq!(m.len(), 16);
+    }
+
+    #[test]
+    fn test_eq_with_tombstones() {
+        let mut fresh = HashMap::with_capacity(8);
+        for i in 0..5 {
+            fresh.insert(i, i * 10);
+        }
+
+        // same contents reached through removals, in a map of another capacity
+        let mut churned = HashMap::with_capacity(32);
+        for i in 0..20 {
+            churned.insert(i, i);
+        }
+        churned.retain(|_, _| false);
+        for i in (0..5).rev() {
+            churned.insert(i, i * 10);
+        }
+        assert!(churned.buckets.iter().any(|s| s.is_deleted()));
+        assert!(fresh == churned);
+        assert!(churned == fresh);
+
+        churned.insert(4, 0);
+        assert!(fresh != churned);
+        churned.remove(&4);
+        assert!(fresh != churned);
+    }
+
+    #[test]
+    fn test_capacity_for() {
+        assert_eq!(HashMap::<u64, u64>::capacity_for(0), 1);
+        assert_eq!(HashMap::<u64, u64>::capacity_for(7), 8);
+        assert_eq!(HashMap::<u64, u64>::capacity_for(8), 16);
+        assert_eq!(HashMap::<u64, u64>::capacity_for_with_load(100, 0.5), 256);
+
+        let capacity = HashMap::<u64, u64>::capacity_for(100);
+        assert_eq!(capacity, 128);
+        let mut m = HashMap::with_capacity(capacity);
+        m.max_load = Some(DEFAULT_LOAD_FACTOR);
+        for i in 0..100 {
+            m.insert(i, i);
+        }
+        assert_eq!(m.cap(), capacity);
+    }
+
+    #[test]
+    fn test_remove_length() {
+        let mut m = HashMap::with_capacity(16);
+        let keys = colliding_keys(&m, 4);
+        for &k in &keys[..3] {
+            m.insert(k, k);
+        }
+
+        // removing the same key twice only counts once
+        assert!(m.remove(&keys[1]));
+        assert!(!m.remove(&keys[1]));
+        assert_eq!(m.len(), 2);
+
+        // a never inserted key walks the whole chain and misses
+        assert!(!m.remove(&keys[3]));
+        assert!(!m.remove(&u64::MAX));
+        assert_eq!(m.len(), 2);
+
+        assert!(m.remove(&keys[0]));
+        assert!(m.remove(&keys[2]));
+        assert!(!m.remove(&keys[2]));
+        assert_eq!(m.len(), 0);
+        assert!(m.is_empty());
+    }
+
+    #[test]
+    fn test_get_deref() {
+        let mut commands: HashMap<String, Box<dyn Fn() -> i32>> = HashMap::new();
+        commands.insert("one".to_string(), Box::new(|| 1));
+        let base = 40;
+        commands.insert("answer".to_string(), Box::new(move || base + 2));
+
+        let answer: &dyn Fn() -> i32 = commands.get_deref("answer").unwrap();
+        assert_eq!(answer(), 42);
+        assert_eq!(commands.get_deref("one").map(|f| f()), Some(1));
+        assert!(commands.get_deref("two").is_none());
+
+        let mut names: HashMap<u32, String> = HashMap::new();
+        names.insert(1, "foo".to_string());
+        assert_eq!(names.get_deref(&1), Some("foo"));
+    }
+
+    #[test]
+    fn test_with_seed() {
+        let build = |seed| {
+            let mut m = HashMap::with_seed(seed);
+            for i in 0..12u64 {
+                m.insert(i, i);
+            }
+            m
+        };
+        let (a, b, c) = (build(7), build(7), build(8));
+        assert!(a.iter().eq(b.iter()));
+
+        // the same seed places every key into the same bucket, another seed doesn't
+        let layout = |m: &HashMap<u64, u64>| {
+            m.buckets
+                .iter()
+                .map(|s| s.key().copied())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(layout(&a), layout(&b));
+        assert_ne!(layout(&a), layout(&c));
+        assert!(a == c);
+    }
+
+    #[test]
+    fn test_bucket_index() {
+        let mut m = HashMap::with_capacity(16);
+        let keys = colliding_keys(&m, 3);
+        for &k in &keys {
+            m.insert(k, ());
+        }
+
+        for &k in &keys {
+            let index = m.bucket_index(&k).unwrap();
+            assert_eq!(m.buckets[index].key(), Some(&k));
+        }
+        assert_eq!(m.bucket_index(&u64::MAX), None);
+    }
+
+    #[test]
+    fn test_count_last() {
+        let mut m = HashMap::with_capacity(64);
+        for i in 0..20u64 {
+            m.insert(i, i * 10);
+        }
+        for i in (0..20u64).step_by(4) {
+            m.remove(&i);
+        }
+
+        assert_eq!(m.keys().count(), m.len());
+        assert_eq!(m.values().count(), 15);
+        assert_eq!(m.iter().len(), 15);
+
+        let mut keys = m.keys();
+        keys.next();
+        keys.next();
+        assert_eq!(keys.len(), 13);
+        assert_eq!(keys.clone().count(), 13);
+
+        // `last` agrees with walking to the end
+        let last = m.iter().fold(None, |_, pair| Some(pair));
+        assert_eq!(m.iter().last(), last);
+        assert_eq!(m.keys().last(), last.map(|(k, _)| k));
+        assert_eq!(m.values().last(), last.map(|(_, v)| v));
+
+        m.clear();
+        assert_eq!(m.keys().count(), 0);
+        assert_eq!(m.values().last(), None);
+    }
+
+    #[test]
+    fn test_map_keys() {
+        let mut m = HashMap::with_load_factor(0.5);
+        m.insert("foo".to_string(), 1);
+        m.insert("Bar".to_string(), 2);
+        m.insert("bar".to_string(), 2);
+
+        let m = m.map_keys(|k| k.to_uppercase());
+        assert_eq!(m.len(), 2);
+        assert_eq!(m.get("FOO"), Some(&1));
+        assert_eq!(m.get("BAR"), Some(&2));
+        assert_eq!(m.get("foo"), None);
+        assert_eq!(m.get("bar"), None);
+        assert_eq!(m.cap(), 16);
+    }
+
+    #[test]
+    fn test_stored_hashes() {
+        use std::cell::Cell;
+        use std::hash::{Hash, Hasher};
+
+        thread_local! {
+            static HASHED: Cell<usize> = const { Cell::new(0) };
+        }
+
+        #[derive(PartialEq, Eq)]
+        struct Counted(String);
+
+        impl Hash for Counted {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                HASHED.with(|c| c.set(c.get() + 1));
+                self.0.hash(state);
+            }
+        }
+
+        let mut m = HashMap::with_load_factor(0.5);
+        let mut resizes = 0;
+        for i in 0..1000 {
+            let cap = m.cap();
+            m.insert(Counted(i.to_string()), i);
+            resizes += usize::from(m.cap() != cap);
+        }
+        assert!(resizes > 0);
+        m.rehash();
+        assert_eq!(HASHED.with(Cell::get), 1000);
+
+        *m.entry(Counted("new".to_string())).or_insert(0) += 1;
+        assert_eq!(HASHED.with(Cell::get), 1001);
+        for i in 0..1000 {
+            assert_eq!(m.get(&Counted(i.to_string())), Some(&i));
+        }
+    }
+
+    #[test]
+    fn test_insert_single_probe() {
+        let mut m = HashMap::with_capacity(16);
+        let keys = colliding_keys(&m, 4);
+        let home = m.find_bucket(&keys[0]);
+
+        // into empty buckets, one after another along the chain
+        for (i, &k) in keys[..3].iter().enumerate() {
+            assert_eq!(m.insert(k, i), (true, None));
+            assert_eq!(m.bucket_index(&k), Some((home + i) % 16));
+        }
+
+        // updating an existing key keeps its bucket
+        assert_eq!(m.insert(keys[2], 20), (true, Some(2)));
+        assert_eq!(m.bucket_index(&keys[2]), Some((home + 2) % 16));
+        assert_eq!(m.len(), 3);
+
+        // a new key reuses the tombstone of its chain, while the keys behind it stay reachable
+        m.retain(|&k, _| k != keys[1]);
+        assert_eq!(m.insert(keys[3], 3), (true, None));
+        assert_eq!(m.bucket_index(&keys[3]), Some((home + 1) % 16));
+        assert_eq!(m.insert(keys[2], 21), (true, Some(20)));
+        assert_eq!(m.bucket_index(&keys[2]), Some((home + 2) % 16));
+        assert_eq!(m.len(), 3);
+
+        // the same goes for the entry API and a growing map
+        m.retain(|&k, _| k != keys[3]);
+        *m.entry(keys[1]).or_insert(0) += 10;
+        assert_eq!(m.bucket_index(&keys[1]), Some((home + 1) % 16));
+        let mut grown = HashMap::with_load_factor(0.5);
+        grown.insert(keys[0], 0);
+        assert_eq!(grown.insert(keys[0], 1), (true, Some(0)));
+        assert_eq!(grown.len(), 1);
+    }
+
+    #[test]
+    fn test_clear_sparse() {
+        let mut m = HashMap::with_capacity(1 << 20);
+        for i in 0..10u64 {
+            m.insert(i, i);
+        }
+        m.retain(|&k, _| k != 3);
+        let tombstone = m.buckets.iter().position(|s| s.is_deleted()).unwrap();
+
+        m.clear();
+        assert_eq!(m.len(), 0);
+        assert_eq!(m.iter().count(), 0);
+        for i in 0..10u64 {
+            assert_eq!(m.get(&i), None);
+        }
+        assert!(m.buckets[tombstone].is_deleted());
+        assert_eq!(m.used.iter().map(|w| w.count_ones()).sum::<u32>(), 1);
+
+        for i in 5..15u64 {
+            m.insert(i, i * 2);
+        }
+        assert_eq!(m.len(), 10);
+        assert_eq!(m.get(&3), None);
+        assert_eq!(m.get(&7), Some(&14));
+    }
+
+    #[test]
+    fn test_get_pair_mut() {
+        let mut m = HashMap::new();
+        m.insert("hello".to_string(), 0);
+
+        let (key, val) = m.get_pair_mut("hello").unwrap();
+        *val = key.len();
+        assert_eq!(m.get("hello"), Some(&5));
+        assert!(m.get_pair_mut("world").is_none());
+    }
+
+    #[test]
+    fn test_backshift_remove() {
+        let mut m = HashMap::with_capacity(16);
+        let keys = colliding_keys(&m, 4);
+        let home = m.find_bucket(&keys[0]);
+        let others = keys_with_home(&m, (home + 2) % 16, 1);
+        for &k in keys.iter().chain(&others) {
+            m.insert(k, k);
+        }
+        // the cluster is keys[0..4] from `home` on, followed by others[0]
+        assert_eq!(m.probe_stats().max_displacement, 3);
+
+        assert!(m.remove(&keys[1]));
+        assert!(!m.buckets.iter().any(|s| s.is_deleted()));
+        assert_eq!(m.bucket_index(&keys[2]), Some((home + 1) % 16));
+        assert_eq!(m.bucket_index(&keys[3]), Some((home + 2) % 16));
+        assert_eq!(m.bucket_index(&others[0]), Some((home + 3) % 16));
+        assert!(m.buckets[(home + 4) % 16].is_empty());
+        assert!(!m.is_used((home + 4) % 16));
+        for &k in keys.iter().filter(|&&k| k != keys[1]).chain(&others) {
+            assert_eq!(m.get(&k), Some(&k));
+        }
+        assert_eq!(m.iter().count(), 4);
+
+        // removing the rest empties the table without a tombstone
+        for k in keys.iter().chain(&others) {
+            m.remove(k);
+        }
+        assert!(m.is_empty());
+        assert!(m.buckets.iter().all(|s| s.is_empty()));
+        assert!(m.used.iter().all(|&w| w == 0));
+    }
+
+    #[test]
+    fn test_get_or() {
+        static DEFAULT_TIMEOUT: u32 = 30;
+
+        let mut config = HashMap::new();
+        config.insert("retries", 3);
+
+        assert_eq!(*config.get_or("retries", &DEFAULT_TIMEOUT), 3);
+        let timeout = config.get_or("timeout", &DEFAULT_TIMEOUT);
+        assert!(std::ptr::eq(timeout, &DEFAULT_TIMEOUT));
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut m = HashMap::with_capacity(16);
+        let stats = m.stats();
+        assert_eq!((stats.len, stats.max_probe_length), (0, 0));
+        assert_eq!(stats.avg_probe_length, 0.0);
+
+        let keys = colliding_keys(&m, 3);
+        for &k in &keys {
+            m.insert(k, ());
+        }
+        for i in 0..5u64 {
+            m.insert(1000 + i, ());
+        }
+        m.remove(&1000);
+        m.retain(|&k, _| k != 1001);
+
+        let stats = m.stats();
+        assert_eq!(stats.capacity, 16);
+        assert_eq!(stats.len, 6);
+        assert_eq!(stats.load_factor, 6.0 / 16.0);
+        assert_eq!(stats.tombstones, 1);
+        assert!(stats.max_probe_length >= 3);
+        assert!(stats.avg_probe_length >= 1.0);
+        assert_eq!(stats.max_probe_length, m.probe_stats().max_displacement + 1);
+    }
+
+    #[test]
+    fn test_try_get() {
+        use std::error::Error;
+
+        fn port<'a>(
+            config: &'a HashMap<String, u16>,
+            key: &'a str,
+        ) -> Result<u16, Box<dyn Error + 'a>> {
+            let port = config.try_get(key)?;
+            Ok(*port + 1)
+        }
+
+        let mut config = HashMap::new();
+        config.insert("port".to_string(), 8079);
+
+        assert_eq!(port(&config, "port").unwrap(), 8080);
+        let err = port(&config, "host").unwrap_err();
+        assert_eq!(err.to_string(), "key not found: host");
+        assert_eq!(config.try_get("host").unwrap_err().key(), "host");
+    }
+
+    #[test]
+    fn test_rehash_with() {
+        let mut m = HashMap::with_capacity(64);
+        for i in 0..40u64 {
+            m.insert(i, i * 3);
+        }
+        let layout =
+            |m: &HashMap<u64, u64>| (0..40u64).map(|i| m.bucket_index(&i)).collect::<Vec<_>>();
+        let before = layout(&m);
+
+        m.rehash_with(SeededState::with_seed(99));
+        assert_eq!(m.len(), 40);
+        assert_eq!(m.cap(), 64);
+        for i in 0..40u64 {
+            assert_eq!(m.get(&i), Some(&(i * 3)));
+        }
+        assert_ne!(layout(&m), before);
+
+        // the new hasher is used from now on
+        let seeded = HashMap::<u64, u64>::with_seed(99);
+        assert_eq!(m.hash_of(&7), seeded.hash_of(&7));
+    }
+
+    #[test]
+    fn test_update_while() {
+        let mut m = HashMap::new();
+        for i in 1..=10u32 {
+            m.insert(i, 10);
+        }
+        let order: Vec<u32> = m.keys().copied().collect();
+
+        // spend a budget of 35 in steps of 10, the fourth entry exhausts it
+        let mut total = 0;
+        m.update_while(|_, v| {
+            total += *v;
+            *v = 0;
+            total <= 35
+        });
+        assert_eq!(total, 40);
+        for (i, k) in order.iter().enumerate() {
+            let expected = if i < 4 { 0 } else { 10 };
+            assert_eq!(m.get(k), Some(&expected));
+        }
+    }
+
+    #[test]
+    fn test_drain_keys() {
+        let mut m = HashMap::with_load_factor(0.5);
+        for i in 0..20 {
+            m.insert(format!("key{}", i), vec![i; 3]);
+        }
+        let cap = m.cap();
+
+        let mut keys = m.drain_keys();
+        keys.sort();
+        let mut expected: Vec<_> = (0..20).map(|i| format!("key{}", i)).collect();
+        expected.sort();
+        assert_eq!(keys, expected);
+
+        assert!(m.is_empty());
+        assert_eq!(m.cap(), cap);
+        assert_eq!(m.iter().count(), 0);
+        assert_eq!(m.get("key1"), None);
+        m.insert("key1".to_string(), vec![]);
+        assert_eq!(m.len(), 1);
+    }
+
+    #[test]
+    fn test_fold_hash() {
+        // hashes differing only in the high half end up in different buckets of a 32-bit index
+        let low = 0x0000_0000_dead_beef_u64;
+        let high = 0x1234_5678_dead_beef_u64;
+        assert_ne!(fold_hash(low) as u32, fold_hash(high) as u32);
+        assert_eq!(fold_hash(low) as u32, 0xdead_beef);
+        assert_eq!(fold_hash(high) as u32, 0xdead_beef ^ 0x1234_5678);
+        assert_ne!(fold_hash(1 << 32) % 16, fold_hash(0) % 16);
+    }
+
+    #[test]
+    fn test_insert_std() {
+        let mut m = HashMap::with_capacity(2);
+        assert_eq!(m.insert_std(1, "a"), None);
+        assert_eq!(m.insert_std(1, "b"), Some("a"));
+        assert_eq!(m.insert_std(2, "c"), None);
+        assert_eq!(m.cap(), 2);
+
+        // the fixed capacity map is full, `insert` fails where `insert_std` grows
+        assert_eq!(m.insert(3, "d"), (false, None));
+        assert_eq!(m.insert_std(3, "d"), None);
+        assert!(m.cap() > 2);
+        assert_eq!(m.len(), 3);
+        assert_eq!(m.get(&1), Some(&"b"));
+        assert_eq!(m.get(&3), Some(&"d"));
+
+        let mut grown = HashMap::with_load_factor(0.5);
+        for i in 0..100 {
+            assert_eq!(grown.insert_std(i, i), None);
+        }
+        assert_eq!(grown.cap(), 256);
+        assert_eq!(grown.insert_std(50, 0), Some(50));
+    }
+
+    #[test]
+    fn test_zero_capacity() {
+        let mut m = HashMap::with_capacity(0);
+        assert_eq!(m.load_factor(), 0.0);
+        assert_eq!(m.get(&1), None);
+        assert!(!m.contains_key(&1));
+        assert!(!m.remove(&1));
+        // the fixed capacity map is full already
+        assert_eq!(m.insert(1, "a"), (false, None));
+        assert_eq!(m.insert_std(1, "a"), None);
+        assert_eq!(m.get(&1), Some(&"a"));
+        assert_eq!(m.insert(1, "b"), (true, Some("a")));
+
+        let mut compact = HashMap::with_capacity_compact(0);
+        assert_eq!(compact.get(&1), None);
+        assert_eq!(compact.insert_std(1, 1), None);
+        assert_eq!(compact.insert_std(2, 2), None);
+        assert_eq!(compact.cap(), 2);
+
+        let sorted = HashMap::from_sorted_by_hash([(1, 1)], 0);
+        assert_eq!(sorted.get(&1), Some(&1));
+    }
+
+    #[test]
+    fn test_raw_slots() {
+        let mut m = HashMap::with_capacity(32);
+        for i in 0..20 {
+            m.insert(i, i);
+        }
+        m.remove(&3);
+        m.retain(|&k, _| k != 5);
+
+        let slots = m.raw_slots();
+        assert_eq!(slots.len(), m.cap());
+        assert_eq!(slots.iter().filter(|s| s.key().is_some()).count(), m.len());
+        assert_eq!(slots.iter().filter(|s| s.is_deleted()).count(), 1);
+    }
+
+    #[test]
+    fn test_map_values() {
+        let mut m = HashMap::with_capacity(32);
+        let keys = colliding_keys(&m, 3);
+        for &k in &keys {
+            m.insert(k, k as u32 % 1000);
+        }
+        m.insert(7, 7);
+        let layout: Vec<_> = m.raw_slots().iter().map(|s| s.key().copied()).collect();
+
+        let m = m.map_values(|v| v.to_string());
+        assert_eq!(m.len(), 4);
+        assert_eq!(m.get(&7).map(String::as_str), Some("7"));
+        for k in &keys {
+            assert_eq!(m.get(k), Some(&(*k as u32 % 1000).to_string()));
+        }
+        let mapped: Vec<_> = m.raw_slots().iter().map(|s| s.key().copied()).collect();
+        assert_eq!(mapped, layout);
+    }
+
+    #[test]
+    fn test_insert_if_absent() {
+        let mut m = HashMap::new();
+        assert!(m.insert_if_absent("foo", 1));
+        assert!(!m.insert_if_absent("foo", 2));
+        assert_eq!(m.get("foo"), Some(&1));
+        assert_eq!(m.len(), 1);
+    }
+
+    #[test]
+    fn test_double_capacity() {
+        assert_eq!(double_capacity(16, 100), 32);
+        assert_eq!(double_capacity(50, 100), 100);
+        let limit = max_capacity::<u64, u64>();
+        assert!(limit < usize::MAX / 2);
+        assert_eq!(double_capacity(limit / 2, limit), limit / 2 * 2);
 
-    #[inline]
-    fn next(&mut self) -> Option<&'a V> {
-        self.inner.next().map(|(_, v)| v)
+        for (capacity, limit) in [(64, 100), (usize::MAX / 2 + 1, usize::MAX), (limit, limit)] {
+            let grown = std::panic::catch_unwind(|| double_capacity(capacity, limit));
+            assert!(grown.is_err());
+        }
+
+        // reserving more than can ever be allocated panics before allocating anything
+        let reserved = std::panic::catch_unwind(|| HashMap::<u64, u64>::new().reserve(usize::MAX));
+        assert!(reserved.is_err());
     }
-}
 
-pub enum Entry<K, V> {
-    Empty,
-    KeyPair(K, V),
-}
+    #[test]
+    fn test_chains() {
+        let mut m = HashMap::with_capacity(4);
+        let keys = colliding_keys(&m, 3);
+        let home = m.find_bucket(&keys[0]);
+        for &k in &keys {
+            m.insert(k, ());
+        }
 
-impl<K, V> Entry<K, V> {
-    #[inline]
-    pub fn key(&self) -> Option<&K> {
-        match *self {
-            Entry::KeyPair(ref k, _) => Some(k),
-            _ => None,
+        let chains: Vec<_> = m.chains().collect();
+        assert_eq!(chains.len(), 1);
+        let (bucket, mut chain) = chains.into_iter().next().unwrap();
+        assert_eq!(bucket, home);
+        chain.sort();
+        let mut expected: Vec<_> = keys.iter().collect();
+        expected.sort();
+        assert_eq!(chain, expected);
+
+        let mut spread = HashMap::with_capacity(64);
+        for i in 0..20u64 {
+            spread.insert(i, ());
         }
+        let homes: Vec<_> = spread.chains().map(|(home, _)| home).collect();
+        assert!(homes.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(
+            spread.chains().map(|(_, keys)| keys.len()).sum::<usize>(),
+            20
+        );
     }
 
-    #[inline]
-    pub fn value(&self) -> Option<&V> {
-        match *self {
-            Entry::KeyPair(_, ref v) => Some(v),
-            _ => None,
+    #[test]
+    fn test_take_value() {
+        let mut states = HashMap::new();
+        states.insert("job", vec![1, 2, 3]);
+
+        let mut state = states.take_value("job").unwrap();
+        assert_eq!(states.get("job"), Some(&vec![]));
+        assert_eq!(states.len(), 1);
+
+        state.push(4);
+        assert_eq!(states.replace_value("job", state), Some(vec![]));
+        assert_eq!(states.get("job"), Some(&vec![1, 2, 3, 4]));
+
+        assert_eq!(states.take_value("other"), None);
+        assert_eq!(states.replace_value("other", vec![5]), None);
+        assert!(!states.contains_key("other"));
+    }
+
+    #[test]
+    fn test_from_iter_refs() {
+        let mut m = HashMap::new();
+        for i in 0..10u32 {
+            m.insert(i, i * i);
         }
+
+        let copy: HashMap<u32, u32> = m.iter().collect();
+        assert!(copy == m);
+
+        let mut more: HashMap<u32, u32> = (20..50).map(|i| (i, i)).collect();
+        assert_eq!(more.len(), 30);
+        more.extend(m.iter());
+        more.extend([(20, 0)]);
+        assert_eq!(more.len(), 40);
+        assert_eq!(more.get(&3), Some(&9));
+        assert_eq!(more.get(&20), Some(&0));
     }
 
-    #[inline]
-    pub fn value_mut(&mut self) -> Option<&mut V> {
-        match *self {
-            Entry::KeyPair(_, ref mut v) => Some(v),
-            _ => None,
+    #[test]
+    fn test_capacity_guard() {
+        let m: HashMap<u64, u64> = HashMap::with_capacity(1 << 16);
+        assert_eq!(m.cap(), 1 << 16);
+
+        let max = max_capacity::<u64, u64>();
+        let err = std::panic::catch_unwind(|| {
+            HashMap::<u64, u64>::with_capacity(usize::MAX);
+        })
+        .unwrap_err();
+        let message = err.downcast_ref::<String>().unwrap();
+        assert_eq!(
+            *message,
+            format!("requested capacity {} exceeds maximum {}", usize::MAX, max)
+        );
+    }
+
+    #[test]
+    fn test_insert_str() {
+        let mut m = HashMap::new();
+        assert_eq!(m.insert_str("foo", 1), None);
+        assert_eq!(m.insert_str("bar", 2), None);
+        assert_eq!(m.insert_str("foo", 3), Some(1));
+
+        assert_eq!(m.len(), 2);
+        assert_eq!(m.get("foo"), Some(&3));
+        assert_eq!(m.get("bar"), Some(&2));
+        assert_eq!(m.get(&"bar".to_string()), Some(&2));
+        assert!(!m.contains_key("baz"));
+    }
+
+    #[test]
+    fn test_bloom() {
+        let mut m = HashMap::with_bloom(2048, 1 << 14);
+        for i in 0..1000u64 {
+            m.insert(i, i);
+        }
+        for i in 0..1000u64 {
+            assert_eq!(m.get(&i), Some(&i));
+        }
+        for i in 1000..100_000u64 {
+            assert!(!m.contains_key(&i));
+            assert_eq!(m.get(&i), None);
         }
+        // most misses are answered by the filter
+        let bloom = m.bloom.as_ref().unwrap();
+        let passed = (1000..100_000u64)
+            .filter(|i| bloom.may_contain(m.hash_of(i)))
+            .count();
+        assert!(passed < 5_000, "{} false positives", passed);
+
+        // removed keys are misses, and keep being misses once the filter is rebuilt
+        for i in 0..500u64 {
+            m.remove(&i);
+        }
+        m.rehash();
+        for i in 0..1000u64 {
+            assert_eq!(m.contains_key(&i), i >= 500);
+        }
+        m.clear();
+        assert!(!m.contains_key(&700));
+        m.insert_std(700, 1);
+        assert_eq!(m.get(&700), Some(&1));
     }
 
-    #[inline]
-    pub fn is_empty(&self) -> bool {
-        match *self {
-            Entry::Empty => true,
-            _ => false,
+    #[test]
+    fn test_remove_at() {
+        // the occupied index lists each live bucket once, and `occupied_at` points back into it
+        fn assert_consistent(m: &HashMap<u32, u32>, expected: usize) {
+            assert_eq!(m.len(), expected);
+            assert_eq!(m.occupied.len(), expected);
+            assert_eq!(m.iter().count(), expected);
+            for (pos, &index) in m.occupied.iter().enumerate() {
+                assert!(matches!(m.buckets[index], Slot::KeyPair(..)));
+                assert_eq!(m.occupied_at[index], pos);
+            }
+        }
+
+        let mut m = HashMap::with_load_factor(0.9);
+        for i in 0..200 {
+            m.insert(i, i);
+        }
+        assert_consistent(&m, 200);
+
+        for i in 0..40 {
+            assert!(m.remove(&i));
+        }
+        assert_consistent(&m, 160);
+
+        let mut visited = 0;
+        m.retain(|&k, _| {
+            visited += 1;
+            k % 2 == 0
+        });
+        assert_eq!(visited, 160);
+        assert_consistent(&m, 80);
+
+        let drained: Vec<_> = m.drain_filter(|&k, _| k % 4 == 0).collect();
+        assert_eq!(drained.len(), 40);
+        assert_consistent(&m, 40);
+
+        if let Entry::Occupied(entry) = m.entry(42) {
+            assert_eq!(entry.remove(), 42);
+        }
+        assert_consistent(&m, 39);
+        for i in 40..200 {
+            assert_eq!(m.contains_key(&i), i % 4 == 2 && i != 42);
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
+    #[test]
+    fn test_get_many_mut() {
+        let mut m = HashMap::new();
+        for (k, v) in [("a", 1), ("b", 2), ("c", 3)] {
+            m.insert(k, v);
+        }
+
+        let [a, c] = m.get_many_mut(["a", "c"]).unwrap();
+        std::mem::swap(a, c);
+        *a += 10;
+        assert_eq!(m.get("a"), Some(&13));
+        assert_eq!(m.get("c"), Some(&1));
 
-    use super::HashMap;
+        assert_eq!(
+            m.get_many_mut(["b", "x", "a"]).err(),
+            Some(GetManyError::Missing(1))
+        );
+        assert_eq!(
+            m.get_many_mut(["a", "b", "a"]).err(),
+            Some(GetManyError::Duplicate(0, 2))
+        );
+        assert_eq!(
+            GetManyError::Duplicate(0, 2).to_string(),
+            "keys 0 and 2 are the same"
+        );
+        assert_eq!(m.get("b"), Some(&2));
+    }
 
     #[test]
-    fn default_new() {
-        let m: HashMap<usize, usize> = HashMap::new();
-        assert_eq!(m.len(), 0);
-        assert_eq!(m.cap(), 100);
-        assert_eq!(m.get(&1), None);
+    fn test_check_invariants() {
+        let mut m = HashMap::with_load_factor(0.75);
+        let mut model = std::collections::HashMap::new();
+        let mut x = 0x2545_f491_4f6c_dd1du64;
+        for step in 0..5000 {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            let key = x % 300;
+            match x >> 60 {
+                0..=8 => assert_eq!(m.insert_std(key, step), model.insert(key, step)),
+                9..=14 => assert_eq!(m.remove(&key), model.remove(&key).is_some()),
+                _ => {
+                    m.retain(|k, _| k % 7 != step % 7);
+                    model.retain(|k, _| k % 7 != step % 7);
+                }
+            }
+            m.check_invariants();
+            assert_eq!(m.len(), model.len());
+        }
+    }
 
-        let mut count = 0;
-        for (_, _) in m.iter() {
-            count += 1;
+    #[test]
+    fn test_insert_all() {
+        let mut m = HashMap::with_load_factor(0.75);
+        m.insert_std("a", 0);
+        let stats = m.insert_all([("a", 1), ("b", 2), ("c", 3), ("b", 4), ("d", 5)]);
+        assert_eq!(stats.inserted, 3);
+        assert_eq!(stats.updated, 2);
+        assert_eq!(m.len(), 4);
+        assert_eq!(m.get("b"), Some(&4));
+
+        let stats = m.insert_all((0..100).map(|i| (if i % 2 == 0 { "a" } else { "e" }, i)));
+        assert_eq!((stats.inserted, stats.updated), (1, 99));
+        assert_eq!(m.get("a"), Some(&98));
+    }
+
+    #[test]
+    fn test_get_probed() {
+        use std::hash::{BuildHasherDefault, Hasher};
+
+        // hashes an integer to itself, so the ideal buckets are known
+        #[derive(Default)]
+        struct Identity(u64);
+
+        impl Hasher for Identity {
+            fn finish(&self) -> u64 {
+                self.0
+            }
+            fn write(&mut self, _: &[u8]) {
+                unimplemented!()
+            }
+            fn write_u64(&mut self, n: u64) {
+                self.0 = n;
+            }
         }
-        assert_eq!(count, 0);
+
+        let mut m =
+            HashMap::with_capacity_and_hasher(16, BuildHasherDefault::<Identity>::default());
+        // a chain of keys in bucket 3
+        for key in [3u64, 19, 35, 51] {
+            m.insert(key, key);
+        }
+        for (displacement, key) in [3u64, 19, 35, 51].into_iter().enumerate() {
+            assert_eq!(m.get_probed(&key), (Some(&key), displacement + 1));
+        }
+        // an absent key of the chain walks it to the empty bucket behind
+        assert_eq!(m.get_probed(&67), (None, 5));
+        assert_eq!(m.get_probed(&8), (None, 1));
     }
 
     #[test]
-    fn test_common() {
-        let mut m = HashMap::with_capacity(3);
-        assert_eq!(m.cap(), 3);
-        assert_eq!(m.len(), 0);
+    fn test_std_conversions() {
+        let std_map: std::collections::HashMap<String, u32> =
+            (0..100).map(|i| (format!("key{}", i), i)).collect();
 
-        // insert key 1, get result
-        assert_eq!(m.insert(1, 100), (true, None));
-        match m.get(&1) {
-            Some(v) => assert_eq!(*v, 100),
-            None => panic!("panicure!"),
+        let mut m = HashMap::from(std_map.clone());
+        assert_eq!(m.len(), 100);
+        for (k, v) in &std_map {
+            assert_eq!(m.get(k), Some(v));
         }
+        // the converted map keeps growing
+        m.insert_std("more".to_string(), 100);
 
-        // insert more keys
-        assert_eq!(m.insert(2, 200), (true, None));
-        assert_eq!(m.insert(3, 300), (true, None));
-        assert_eq!(m.len(), 3);
+        let back = std::collections::HashMap::from(m);
+        assert_eq!(back.len(), 101);
+        assert_eq!(back.get("more"), Some(&100));
+        assert!(std_map.iter().all(|(k, v)| back.get(k) == Some(v)));
+        assert!(HashMap::<u32, u32>::from(std::collections::HashMap::new()).is_empty());
+    }
 
-        // our hashmap capacity is 3, now it's filled, can't be insert anymore
-        assert_eq!(m.insert(4, 400), (false, None));
+    #[test]
+    fn test_would_resize() {
+        // grows past 12 entries
+        let mut m = HashMap::with_load_factor(0.75);
+        for i in 0..11u32 {
+            m.insert_std(i, i);
+        }
+        assert!(!m.would_resize(&11));
+        m.insert_std(11, 11);
 
-        // although it's filled, but we can update the existing key, return true
-        assert_eq!(m.insert(1, 1000), (true, Some(100)));
-        // assert the new value
-        match m.get(&1) {
-            Some(v) => assert_eq!(*v, 1000),
-            None => panic!("panicure!"),
+        assert!(!m.would_resize(&3));
+        assert!(m.would_resize(&12));
+        m.insert_std(3, 30);
+        assert_eq!(m.cap(), 16);
+        m.insert_std(12, 12);
+        assert_eq!(m.cap(), 32);
+        assert!(!m.would_resize(&13));
+    }
+
+    #[test]
+    fn test_iter_by_bucket() {
+        let mut m = HashMap::with_capacity(64);
+        for i in 0..40u32 {
+            m.insert(i, i);
         }
+        m.remove(&7);
 
-        // remove key 1, vacated a bucket
-        assert_eq!(m.remove(&1), true);
-        match m.get(&1) {
-            Some(y) => panic!("key {} must not exists!", y),
-            None => {}
+        let buckets: Vec<_> = m
+            .iter_by_bucket()
+            .map(|(k, _)| m.bucket_index(k).unwrap())
+            .collect();
+        assert_eq!(buckets.len(), 39);
+        assert!(buckets.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(m.iter_by_bucket().len(), 39);
+    }
+
+    #[test]
+    fn test_iter_from() {
+        let mut m = HashMap::with_capacity(256);
+        for i in 0..100u32 {
+            m.insert(i, i);
         }
-        assert_eq!(m.contains_key(&1), false);
-        assert_eq!(m.len(), 2);
-        for (&k, &v) in m.iter() {
-            match k {
-                2 => assert_eq!(v, 200),
-                3 => assert_eq!(v, 300),
-                _ => {}
+        m.retain(|&k, _| k % 10 != 0);
+
+        // a scan in chunks of 25 entries, resuming where the previous chunk stopped
+        let mut seen = Vec::new();
+        let mut start = 0;
+        loop {
+            let mut chunk = m.iter_from(start);
+            seen.extend(chunk.by_ref().take(25).map(|(&k, _)| k));
+            start = chunk.next_bucket().unwrap();
+            if chunk.len() == 0 {
+                break;
             }
         }
+        seen.sort_unstable();
+        let expected: Vec<_> = (0..100).filter(|k| k % 10 != 0).collect();
+        assert_eq!(seen, expected);
 
-        // we can insert a key/value now
-        assert_eq!(m.insert(4, 400), (true, None));
+        // two halves of the buckets cover every entry once
+        let mid = m.cap() / 2;
+        let first = m
+            .iter_from(0)
+            .take_while(|(k, _)| m.bucket_index(*k).unwrap() < mid);
+        let second = m.iter_from(mid);
+        assert_eq!(
+            second.len(),
+            m.keys()
+                .filter(|k| m.bucket_index(*k).unwrap() >= mid)
+                .count()
+        );
+        let mut halves: Vec<_> = first.chain(second).map(|(&k, _)| k).collect();
+        halves.sort_unstable();
+        assert_eq!(halves, expected);
+
+        assert_eq!(m.iter_from(1000).next(), None);
+        assert_eq!(m.iter().next_bucket(), None);
+        assert_eq!(m.iter_from(0).count(), 90);
+        assert_eq!(
+            m.iter_from(0).last(),
+            m.iter_from(0).fold(None, |_, e| Some(e))
+        );
+    }
+
+    #[test]
+    fn test_auto_shrink() {
+        let mut m = HashMap::with_load_factor(0.75);
+        m.auto_shrink(true);
+        for i in 0..1000u32 {
+            m.insert(i, i);
+        }
+        assert_eq!(m.cap(), 2048);
+
+        for i in 0..900u32 {
+            m.remove(&i);
+        }
+        assert_eq!(m.cap(), 512);
+        m.retain(|&k, _| k % 2 == 0);
+        assert_eq!(m.cap(), 256);
+        m.drain_filter(|&k, _| k < 980).for_each(drop);
+        assert_eq!(m.cap(), 64);
+        assert_eq!(m.len(), 10);
+        for i in 980..1000u32 {
+            assert_eq!(m.get(&i), (i % 2 == 0).then_some(&i));
+        }
+
+        // never below the default capacity
+        m.clear();
+        m.insert(1, 1);
+        m.remove(&1);
+        assert_eq!(m.cap(), HashMap::<u32, u32>::DEFAULT_CAPACITY);
+
+        // off by default
+        let mut m = HashMap::with_load_factor(0.75);
+        for i in 0..1000u32 {
+            m.insert(i, i);
+        }
+        m.retain(|_, _| false);
+        assert_eq!(m.cap(), 2048);
+    }
+
+    #[test]
+    fn test_set_keys() {
+        let a: HashMap<u32, &str> = (0..10).map(|i| (i, "a")).collect();
+        let b: HashMap<u32, u64> = (5..15).map(|i| (i, i as u64)).collect();
+
+        let mut both: Vec<_> = a.intersection_keys(&b).copied().collect();
+        both.sort_unstable();
+        assert_eq!(both, [5, 6, 7, 8, 9]);
+
+        let mut only_a: Vec<_> = a.difference_keys(&b).copied().collect();
+        only_a.sort_unstable();
+        assert_eq!(only_a, [0, 1, 2, 3, 4]);
+
+        let mut only_b: Vec<_> = b.difference_keys(&a).copied().collect();
+        only_b.sort_unstable();
+        assert_eq!(only_b, [10, 11, 12, 13, 14]);
+
+        let empty = HashMap::<u32, ()>::new();
+        assert_eq!(a.intersection_keys(&empty).count(), 0);
+        assert_eq!(a.difference_keys(&empty).count(), 10);
+    }
+
+    #[test]
+    fn test_from_iter_unique() {
+        let m = HashMap::from_iter_unique([("a", 1), ("b", 2), ("c", 3)]).unwrap();
         assert_eq!(m.len(), 3);
-        for (&k, &v) in m.iter() {
-            match k {
-                2 => assert_eq!(v, 200),
-                3 => assert_eq!(v, 300),
-                4 => assert_eq!(v, 400),
-                _ => {}
-            }
+        assert_eq!(m.get("b"), Some(&2));
+
+        let err = HashMap::from_iter_unique([("a", 1), ("b", 2), ("a", 3), ("b", 4)])
+            .err()
+            .unwrap();
+        assert_eq!(err, DuplicateKey("a"));
+        assert_eq!(err.to_string(), "duplicate key: a");
+
+        let many = HashMap::from_iter_unique((0..1000).map(|i| (i, i))).unwrap();
+        assert_eq!(many.len(), 1000);
+    }
+
+    #[test]
+    fn test_next_capacity() {
+        let mut m = HashMap::with_load_factor(0.5);
+        assert_eq!(m.next_capacity(), 32);
+        for i in 0..9u32 {
+            let predicted = m.next_capacity();
+            let before = m.cap();
+            m.insert(i, i);
+            assert!(m.cap() == before || m.cap() == predicted);
         }
+        assert_eq!(m.cap(), 32);
+        assert_eq!(m.next_capacity(), 64);
+
+        assert_eq!(HashMap::<u32, u32>::with_capacity(0).next_capacity(), 1);
+        assert_eq!(HashMap::<u32, u32>::with_capacity(100).next_capacity(), 200);
     }
 
     #[test]
-    fn test_get_mut() {
-        let mut m = HashMap::new();
-        m.insert("foo", 42);
-        m.insert("bar", 43);
-        match m.get("foo") {
-            Some(v) => assert_eq!(*v, 42),
-            None => panic!("panicure!"),
+    fn test_hash_of() {
+        let a = HashMap::<String, u32>::with_seed(7);
+        let b = HashMap::<String, u32>::with_seed(7);
+        let c = HashMap::<String, u32>::with_seed(8);
+
+        assert_eq!(a.hash_of("key"), a.hash_of("key"));
+        assert_eq!(a.hash_of("key"), a.hash_of(&"key".to_string()));
+        assert_eq!(a.hash_of("key"), b.hash_of("key"));
+        assert_ne!(a.hash_of("key"), c.hash_of("key"));
+
+        // shard by hash
+        let shards = 4;
+        let shard = |k: &str| (a.hash_of(k) % shards) as usize;
+        assert_eq!(shard("key"), (b.hash_of("key") % shards) as usize);
+    }
+
+    #[test]
+    fn test_transform() {
+        let mut m = HashMap::with_load_factor(0.75);
+        for (k, v) in [("a", 0), ("b", 1), ("c", 2), ("d", 0)] {
+            m.insert(k.to_string(), v);
         }
 
-        // change value of key `foo`
-        match m.get_mut("foo") {
-            Some(v) => *v = 40,
-            None => panic!("panicure!"),
+        let t = m.transform(|k, v| (v != 0).then(|| (k + "!", v * 2)));
+        assert_eq!(t.len(), 2);
+        assert_eq!(t.get("b!"), Some(&2));
+        assert_eq!(t.get("c!"), Some(&4));
+        assert!(!t.contains_key("a!"));
+        assert!(!t.contains_key("b"));
+
+        // colliding keys keep one of the values
+        let t = t.transform(|_, v| Some((String::new(), v)));
+        assert_eq!(t.len(), 1);
+        assert!(matches!(t.get(""), Some(2 | 4)));
+    }
+
+    #[test]
+    fn test_memory_usage() {
+        let mut m = HashMap::with_load_factor(0.5);
+        let small = m.memory_usage();
+        assert!(small >= 16 * std::mem::size_of::<Slot<u64, u64>>());
+
+        for i in 0..500u64 {
+            m.insert(i, i);
         }
+        assert_eq!(m.cap(), 1024);
+        // 64 times the buckets, plus the occupied index of the entries
+        let large = m.memory_usage();
+        assert!(
+            large >= small * 60 && large <= small * 70,
+            "{} {}",
+            small,
+            large
+        );
 
-        // get the changed value
-        match m.get("foo") {
-            Some(v) => assert_eq!(*v, 40),
-            None => panic!("panicure!"),
+        let bloom = HashMap::<u64, u64>::with_bloom(16, 1 << 16);
+        assert_eq!(bloom.memory_usage(), small + (1 << 16) / 8);
+    }
+
+    #[test]
+    fn test_free_slots() {
+        let mut m = HashMap::with_capacity(64);
+        for i in 0..40u32 {
+            m.insert(i, i);
+        }
+        assert_eq!(m.free_slots().count() + m.len(), m.cap());
+        assert_eq!(m.tombstone_count(), 0);
+
+        for i in 0..5u32 {
+            m.remove(&i);
+        }
+        m.retain(|&k, _| k % 3 != 0);
+        assert_eq!(m.len(), 23);
+        assert_eq!(m.free_slots().count() + m.len(), m.cap());
+        assert!(m.tombstone_count() > 0);
+        assert_eq!(m.tombstone_count(), m.stats().tombstones);
+        for index in m.free_slots() {
+            assert!(!matches!(m.raw_slots()[index], Slot::KeyPair(..)));
         }
+
+        m.rehash();
+        assert_eq!(m.tombstone_count(), 0);
+        assert_eq!(m.free_slots().count(), 41);
     }
 
     #[test]
-    fn test_iter_mut() {
+    fn test_insert_indexed() {
         let mut m = HashMap::new();
-        m.insert("foo", 42);
-        m.insert("bar", 43);
-        for (_, v) in m.iter_mut() {
-            *v += 1;
+        for i in 0..100u32 {
+            let (old, index) = m.insert_indexed(i, i);
+            assert_eq!(old, None);
+            assert_eq!(m.bucket_index(&i), Some(index));
         }
+        let (old, index) = m.insert_indexed(42, 0);
+        assert_eq!(old, Some(42));
+        assert_eq!(m.bucket_index(&42), Some(index));
+        assert_eq!(m.raw_slots()[index].value(), Some(&0));
+    }
 
-        for (&k, &v) in m.iter() {
-            match k {
-                "foo" => assert_eq!(v, 43),
-                "bar" => assert_eq!(v, 44),
-                _ => unreachable!(),
-            }
+    #[test]
+    fn test_eq_ignoring() {
+        let mut a = HashMap::with_load_factor(0.75);
+        let mut b = HashMap::with_load_factor(0.75);
+        for m in [&mut a, &mut b] {
+            m.insert("name".to_string(), "x".to_string());
+            m.insert("size".to_string(), "3".to_string());
         }
+        a.insert("updated".to_string(), "10:00".to_string());
+        b.insert("updated".to_string(), "10:05".to_string());
+
+        assert!(a != b);
+        assert!(a.eq_ignoring(&b, ["updated"]));
+        assert!(!a.eq_ignoring(&b, ["size"]));
+
+        // an ignored key present on one side only
+        b.insert("created".to_string(), "9:00".to_string());
+        assert!(!a.eq_ignoring(&b, ["updated"]));
+        assert!(a.eq_ignoring(&b, ["updated", "created"]));
     }
 
     #[test]
-    fn test_keys_values() {
-        let mut map = HashMap::new();
-        map.insert("foo", 42);
-        map.insert("bar", 43);
+    fn test_compact() {
+        let mut m = HashMap::with_capacity_compact(2);
+        assert_eq!(m.cap(), 2);
+        assert_eq!(m.raw_slots().len(), 2);
 
-        for key in map.keys() {
-            println!("{}", key);
+        // two keys with the same ideal bucket
+        let keys = colliding_keys(&m, 2);
+        m.insert(keys[0], "a");
+        m.insert(keys[1], "b");
+        assert_eq!(m.cap(), 2);
+        assert_eq!(m.get(&keys[0]), Some(&"a"));
+        assert_eq!(m.get(&keys[1]), Some(&"b"));
+
+        // grows by one bucket while small, then to powers of two
+        assert_eq!(m.next_capacity(), 3);
+        m.insert_std(100, "c");
+        assert_eq!(m.cap(), 3);
+        for i in 1000..1013 {
+            m.insert_std(i, "d");
         }
+        assert_eq!(m.cap(), 16);
+        assert_eq!(m.next_capacity(), 32);
+        m.insert_std(200, "e");
+        assert_eq!(m.cap(), 32);
+        assert_eq!(m.len(), 17);
 
-        for val in map.values() {
-            println!("{}", val);
+        assert_eq!(m.get(&keys[1]), Some(&"b"));
+        assert_eq!(m.get(&200), Some(&"e"));
+
+        // a compact capacity past the threshold rounds up to a power of two
+        let mut m = HashMap::with_capacity_compact(20);
+        assert_eq!(m.next_capacity(), 32);
+        for i in 0..21 {
+            m.insert_std(i, ());
         }
+        assert_eq!(m.cap(), 32);
     }
 
     #[test]
-    fn test_eq() {
-        let mut map1 = HashMap::new();
-        map1.insert("foo", 42);
-        map1.insert("bar", 43);
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "pairs aren't sorted by hash")]
+    fn test_from_sorted_by_hash_unsorted() {
+        let hasher = HashMap::<u64, u64>::new();
+        let mut pairs: Vec<_> = (0..10u64).map(|i| (i, i)).collect();
+        pairs.sort_by_key(|(k, _)| std::cmp::Reverse(hasher.hash_of(k)));
+        HashMap::from_sorted_by_hash(pairs, 16);
+    }
 
-        let mut map2 = HashMap::new();
-        map2.insert("foo", 42);
-        map2.insert("bar", 43);
+    #[test]
+    fn test_from_sorted_by_hash() {
+        let hasher = HashMap::<u64, u64>::new();
+        let mut pairs: Vec<_> = (0..900u64).map(|i| (i, i)).collect();
+        pairs.push((7, 700));
+        pairs.sort_by_key(|(k, _)| hasher.hash_of(k));
 
-        let eq = map1 == map2;
-        assert_eq!(true, eq)
+        let sorted = HashMap::from_sorted_by_hash(pairs, 1024);
+        assert_eq!(sorted.len(), 900);
+        assert_eq!(sorted.cap(), 1024);
+        assert_eq!(sorted.get(&7), Some(&700));
+        for i in (0..900u64).filter(|&i| i != 7) {
+            assert_eq!(sorted.get(&i), Some(&i));
+        }
+
+        let mut random = HashMap::with_capacity(1024);
+        for i in 0..900u64 {
+            random.insert(i * 7919 % 900, 0);
+        }
+        let (sorted, random) = (sorted.probe_stats(), random.probe_stats());
+        assert!(sorted.total_displacement <= random.total_displacement);
+        assert!(sorted.max_displacement <= random.max_displacement);
+
+        // grows past the capacity
+        let mut pairs: Vec<_> = (0..100u64).map(|i| (i, i)).collect();
+        pairs.sort_by_key(|(k, _)| hasher.hash_of(k));
+        let grown = HashMap::from_sorted_by_hash(pairs, 8);
+        assert_eq!(grown.len(), 100);
+        assert!(grown.cap() >= 100);
     }
 }
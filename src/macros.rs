@@ -0,0 +1,41 @@
+// Defines a lazily built `static` lookup table of `HashMap<&'static str, V>`. Hashing isn't
+// `const`, so the map is built from the literal pairs on first access:
+//
+// static_map! {
+//     static COLORS: u32 = {
+//         "red" => 0xff0000,
+//         "green" => 0x00ff00,
+//     };
+// }
+//
+// assert_eq!(COLORS.get("red"), Some(&0xff0000));
+#[macro_export]
+macro_rules! static_map {
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $val:ty = { $($key:expr => $value:expr),* $(,)? };) => {
+        $(#[$attr])*
+        $vis static $name: ::std::sync::LazyLock<$crate::HashMap<&'static str, $val>> =
+            ::std::sync::LazyLock::new(|| {
+                ::std::iter::FromIterator::from_iter([$(($key, $value)),*])
+            });
+    };
+}
+
+#[cfg(test)]
+mod tests {
+
+    static_map! {
+        static STATUS: u16 = {
+            "ok" => 200,
+            "not found" => 404,
+            "teapot" => 418,
+        };
+    }
+
+    #[test]
+    fn test_static_map() {
+        assert_eq!(STATUS.len(), 3);
+        assert_eq!(STATUS.get("teapot"), Some(&418));
+        assert_eq!(STATUS.get("ok"), Some(&200));
+        assert!(!STATUS.contains_key("gone"));
+    }
+}
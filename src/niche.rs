@@ -0,0 +1,225 @@
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash};
+
+use super::{fold_hash, SeededState, DEFAULT_LOAD_FACTOR};
+
+// A key type with a value that never occurs as a key, which a `NicheHashMap` stores in its
+// empty buckets instead of tagging every bucket like `Slot` does
+pub trait Niche: Copy + Eq {
+    const EMPTY: Self;
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        *self == Self::EMPTY
+    }
+}
+
+macro_rules! niche_max {
+    ($($t:ty),*) => {
+        $(impl Niche for $t {
+            const EMPTY: $t = <$t>::MAX;
+        })*
+    };
+}
+
+niche_max!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+// A map of bare `(K, V)` pairs for small keys like integers, where the `EMPTY` key marks the
+// free buckets. Without the discriminant of `Slot` e.g. a `(u32, u32)` bucket takes 8 bytes
+// instead of 12. It uses linear probing, and a removal shifts the rest of the probe sequence
+// back, so there are no tombstones. Free buckets hold `V::default()`, and `EMPTY` itself
+// can't be inserted
+pub struct NicheHashMap<K, V, S = SeededState> {
+    buckets: Vec<(K, V)>,
+    length: usize,
+    hash_builder: S,
+}
+
+impl<K, V> NicheHashMap<K, V>
+where
+    K: Niche + Hash,
+    V: Default,
+{
+    pub fn new() -> NicheHashMap<K, V> {
+        NicheHashMap::with_capacity(0)
+    }
+
+    // A map holding `capacity` entries before it grows
+    pub fn with_capacity(capacity: usize) -> NicheHashMap<K, V> {
+        let buckets = (capacity as f64 / DEFAULT_LOAD_FACTOR).ceil() as usize;
+        NicheHashMap {
+            buckets: empty_buckets(buckets.max(8).next_power_of_two()),
+            length: 0,
+            hash_builder: SeededState::default(),
+        }
+    }
+}
+
+impl<K, V> Default for NicheHashMap<K, V>
+where
+    K: Niche + Hash,
+    V: Default,
+{
+    fn default() -> Self {
+        NicheHashMap::new()
+    }
+}
+
+impl<K, V, S> NicheHashMap<K, V, S>
+where
+    K: Niche + Hash,
+    V: Default,
+    S: BuildHasher,
+{
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn cap(&self) -> usize {
+        self.buckets.len()
+    }
+
+    // Inserts the pair and returns the previous value of the key, panics if the key is `EMPTY`
+    pub fn insert(&mut self, key: K, val: V) -> Option<V> {
+        assert!(
+            !key.is_empty(),
+            "the EMPTY key of a NicheHashMap can't be inserted"
+        );
+        if let Some(index) = self.find(&key) {
+            return Some(std::mem::replace(&mut self.buckets[index].1, val));
+        }
+        if (self.length + 1) as f64 > self.cap() as f64 * DEFAULT_LOAD_FACTOR {
+            self.resize(self.cap() * 2);
+        }
+        let mut index = self.home_bucket(&key);
+        while !self.buckets[index].0.is_empty() {
+            index = (index + 1) & (self.cap() - 1);
+        }
+        self.buckets[index] = (key, val);
+        self.length += 1;
+        None
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.find(key).map(|index| &self.buckets[index].1)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.find(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let mut hole = self.find(key)?;
+        let (_, val) = std::mem::replace(&mut self.buckets[hole], (K::EMPTY, V::default()));
+        self.length -= 1;
+
+        // moves back every following entry of the cluster that may live in the hole
+        let mask = self.cap() - 1;
+        let mut index = (hole + 1) & mask;
+        while !self.buckets[index].0.is_empty() {
+            let home = self.home_bucket(&self.buckets[index].0);
+            if (index.wrapping_sub(home) & mask) >= (index.wrapping_sub(hole) & mask) {
+                self.buckets.swap(hole, index);
+                hole = index;
+            }
+            index = (index + 1) & mask;
+        }
+        Some(val)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.buckets
+            .iter()
+            .filter(|(k, _)| !k.is_empty())
+            .map(|(k, v)| (k, v))
+    }
+
+    // The bucket of the key, the probe ends at the first free bucket
+    fn find<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let mask = self.cap() - 1;
+        let mut index = fold_hash(self.hash_builder.hash_one(key)) & mask;
+        loop {
+            let k = &self.buckets[index].0;
+            if k.is_empty() {
+                return None;
+            }
+            if k.borrow() == key {
+                return Some(index);
+            }
+            index = (index + 1) & mask;
+        }
+    }
+
+    #[inline]
+    fn home_bucket(&self, key: &K) -> usize {
+        fold_hash(self.hash_builder.hash_one(key)) & (self.cap() - 1)
+    }
+
+    fn resize(&mut self, capacity: usize) {
+        let old = std::mem::replace(&mut self.buckets, empty_buckets(capacity));
+        for (key, val) in old.into_iter().filter(|(k, _)| !k.is_empty()) {
+            let mut index = self.home_bucket(&key);
+            while !self.buckets[index].0.is_empty() {
+                index = (index + 1) & (capacity - 1);
+            }
+            self.buckets[index] = (key, val);
+        }
+    }
+}
+
+fn empty_buckets<K: Niche, V: Default>(capacity: usize) -> Vec<(K, V)> {
+    std::iter::repeat_with(|| (K::EMPTY, V::default()))
+        .take(capacity)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::NicheHashMap;
+    use crate::Slot;
+    use std::mem::size_of;
+
+    #[test]
+    fn test_niche_map() {
+        let mut m = NicheHashMap::new();
+        let mut model = std::collections::HashMap::new();
+        for i in 0..2000u32 {
+            let key = i.wrapping_mul(2_654_435_761) % 700;
+            if i % 3 == 0 {
+                assert_eq!(m.remove(&key), model.remove(&key));
+            } else {
+                assert_eq!(m.insert(key, i), model.insert(key, i));
+            }
+        }
+        assert_eq!(m.len(), model.len());
+        for key in 0..700u32 {
+            assert_eq!(m.get(&key), model.get(&key));
+        }
+        assert_eq!(m.iter().count(), model.len());
+        assert!(!m.contains_key(&u32::MAX));
+
+        assert!(size_of::<(u32, u32)>() < size_of::<Slot<u32, u32>>());
+        assert_eq!(m.buckets.len() * size_of::<(u32, u32)>(), m.cap() * 8);
+    }
+}
@@ -0,0 +1,208 @@
+use std::hash::{BuildHasher, Hash};
+
+use super::{HashMap, Slot, DEFAULT_LOAD_FACTOR};
+
+// A mutation recorded by a map in `record_ops` mode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op<K, V> {
+    // a new key was inserted, or the value of a present one overwritten, with the new value
+    Insert(K, V),
+    Remove(K),
+    // a resize or rehash rebuilt the buckets with this capacity
+    Resize(usize),
+    Clear,
+}
+
+// The recorded operations along with the `Clone` impls of the keys and values, captured by
+// `record_ops`, so the mutating methods don't need `Clone` bounds to record
+pub(crate) struct OpLog<K, V> {
+    ops: Vec<Op<K, V>>,
+    clone_key: fn(&K) -> K,
+    clone_val: fn(&V) -> V,
+}
+
+impl<K, V> OpLog<K, V> {
+    pub(crate) fn insert(&mut self, key: &K, val: &V) {
+        let op = Op::Insert((self.clone_key)(key), (self.clone_val)(val));
+        self.ops.push(op);
+    }
+
+    pub(crate) fn remove(&mut self, key: &K) {
+        let op = Op::Remove((self.clone_key)(key));
+        self.ops.push(op);
+    }
+
+    pub(crate) fn push(&mut self, op: Op<K, V>) {
+        self.ops.push(op);
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S> {
+    // Records the insertion into the bucket `index`, if the map records
+    pub(crate) fn record_insert(&mut self, index: usize) {
+        if let (Some(log), Slot::KeyPair(ref k, ref v)) =
+            (self.recorder.as_mut(), &self.buckets[index])
+        {
+            log.insert(k, v);
+        }
+    }
+}
+
+impl<K, V> HashMap<K, V>
+where
+    K: Hash + Eq,
+{
+    // Rebuilds a map by applying the operations of `op_log` in order to a new map, e.g. to
+    // reproduce a reported corruption
+    pub fn replay<I>(ops: I) -> HashMap<K, V>
+    where
+        I: IntoIterator<Item = Op<K, V>>,
+    {
+        let mut map = HashMap::with_load_factor(DEFAULT_LOAD_FACTOR);
+        for op in ops {
+            match op {
+                Op::Insert(key, val) => {
+                    map.insert_std(key, val);
+                }
+                Op::Remove(key) => {
+                    map.remove(&key);
+                }
+                Op::Resize(capacity) => map.resize(capacity.max(map.len())),
+                Op::Clear => map.clear(),
+            }
+        }
+        map
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher,
+{
+    // Turns recording of the mutations into `op_log` on or off, for debugging. Recording starts
+    // with the current capacity and entries, so `replay` of the log rebuilds the map. Every
+    // overwrite by a map or entry method is recorded, but not values changed through a handed
+    // out `&mut V`, e.g. from `get_mut` or `and_modify`. Turning it off drops the log
+    pub fn record_ops(&mut self, record: bool) {
+        if !record {
+            self.recorder = None;
+            return;
+        }
+        let mut log = OpLog {
+            ops: vec![Op::Resize(self.cap())],
+            clone_key: K::clone,
+            clone_val: V::clone,
+        };
+        for (k, v) in self.iter() {
+            log.insert(k, v);
+        }
+        self.recorder = Some(log);
+    }
+
+    // The operations recorded since `record_ops(true)`, empty when the map doesn't record
+    pub fn op_log(&self) -> &[Op<K, V>] {
+        self.recorder.as_ref().map_or(&[], |log| &log.ops)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{HashMap, Op};
+    use crate::Entry;
+
+    // A recording map of a few entries
+    fn recording() -> HashMap<String, u32> {
+        let mut m = HashMap::with_load_factor(0.75);
+        m.record_ops(true);
+        for i in 0..10 {
+            m.insert_std(i.to_string(), i);
+        }
+        m
+    }
+
+    fn assert_replays(m: &HashMap<String, u32>) {
+        assert!(HashMap::replay(m.op_log().to_vec()) == *m);
+    }
+
+    #[test]
+    fn test_op_log() {
+        let mut m = HashMap::with_capacity(8);
+        m.insert(0u32, 0u32);
+        m.record_ops(true);
+        for i in 1..40 {
+            m.insert_std(i, i * 2);
+            if i % 3 == 0 {
+                m.remove(&(i / 2));
+            }
+        }
+        m.insert(5, 50);
+        m.entry(100).or_insert(1);
+        m.retain(|&k, _| k % 5 != 0);
+        m.rehash();
+
+        let log = m.op_log();
+        assert_eq!(log[..2], [Op::Resize(8), Op::Insert(0, 0)]);
+        assert!(log.contains(&Op::Remove(1)));
+        assert!(log.iter().any(|op| matches!(op, Op::Resize(c) if *c > 8)));
+
+        let replayed = HashMap::replay(log.to_vec());
+        assert!(replayed == m);
+        assert_eq!(replayed.cap(), m.cap());
+
+        m.record_ops(false);
+        assert!(m.op_log().is_empty());
+    }
+
+    #[test]
+    fn test_op_log_upsert() {
+        let mut m = recording();
+        m.upsert("3".to_string(), 30);
+        assert_replays(&m);
+    }
+
+    #[test]
+    fn test_op_log_modify_or_insert() {
+        let mut m = recording();
+        m.modify_or_insert("4".to_string(), |v| *v += 40, 0);
+        m.modify_or_insert("new".to_string(), |v| *v += 1, 7);
+        assert_replays(&m);
+    }
+
+    #[test]
+    fn test_op_log_replace_entry() {
+        let mut m = recording();
+        if let Entry::Occupied(entry) = m.entry("5".to_string()) {
+            entry.replace_entry("5".to_string(), 50);
+        }
+        assert_replays(&m);
+    }
+
+    #[test]
+    fn test_op_log_insert_str() {
+        let mut m = recording();
+        m.insert_str("6", 60);
+        m.insert_str("other", 1);
+        assert_replays(&m);
+    }
+
+    #[test]
+    fn test_op_log_merge_with() {
+        let mut m = recording();
+        let mut other = HashMap::with_load_factor(0.75);
+        other.insert_std("7".to_string(), 70);
+        other.insert_std("merged".to_string(), 2);
+        m.merge_with(other, |_, v, new| *v += new);
+        assert_replays(&m);
+    }
+
+    #[test]
+    fn test_op_log_replace_value() {
+        let mut m = recording();
+        m.replace_value("8", 80);
+        m.take_value("9");
+        assert_replays(&m);
+    }
+}
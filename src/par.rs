@@ -0,0 +1,119 @@
+use rayon::iter::plumbing::UnindexedConsumer;
+use rayon::prelude::*;
+
+use super::{HashMap, Slot};
+
+// Parallel iterator over all key-value pairs, the element type is `(&'a K, &'a V)`
+pub struct ParIter<'a, K, V> {
+    buckets: &'a [Slot<K, V>],
+}
+
+impl<'a, K: Sync, V: Sync> ParallelIterator for ParIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        // rayon splits the bucket slice into chunks, every chunk skips its own empty buckets
+        self.buckets
+            .par_iter()
+            .filter_map(|entry| match *entry {
+                Slot::KeyPair(ref k, ref v) => Some((k, v)),
+                _ => None,
+            })
+            .drive_unindexed(consumer)
+    }
+}
+
+// Parallel iterator over all values, the element type is `&'a V`
+pub struct ParValues<'a, K, V> {
+    inner: ParIter<'a, K, V>,
+}
+
+impl<'a, K: Sync, V: Sync> ParallelIterator for ParValues<'a, K, V> {
+    type Item = &'a V;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.inner.map(|(_, v)| v).drive_unindexed(consumer)
+    }
+}
+
+// Parallel iterator over mutable references to all values, the element type is `&'a mut V`
+pub struct ParValuesMut<'a, K, V> {
+    buckets: &'a mut [Slot<K, V>],
+}
+
+impl<'a, K: Send, V: Send> ParallelIterator for ParValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.buckets
+            .par_iter_mut()
+            .filter_map(|entry| entry.value_mut())
+            .drive_unindexed(consumer)
+    }
+}
+
+impl<'a, K: Sync, V: Sync, S> IntoParallelIterator for &'a HashMap<K, V, S> {
+    type Iter = ParIter<'a, K, V>;
+    type Item = (&'a K, &'a V);
+
+    #[inline]
+    fn into_par_iter(self) -> ParIter<'a, K, V> {
+        ParIter {
+            buckets: &self.buckets,
+        }
+    }
+}
+
+impl<K: Sync, V: Sync, S> HashMap<K, V, S> {
+    // A parallel iterator visiting all values
+    #[inline]
+    pub fn par_values(&self) -> ParValues<'_, K, V> {
+        ParValues {
+            inner: self.into_par_iter(),
+        }
+    }
+}
+
+impl<K: Send, V: Send, S> HashMap<K, V, S> {
+    // A parallel iterator visiting all values mutably
+    #[inline]
+    pub fn par_values_mut(&mut self) -> ParValuesMut<'_, K, V> {
+        ParValuesMut {
+            buckets: &mut self.buckets,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::HashMap;
+    use rayon::prelude::*;
+
+    #[test]
+    fn test_par_iter() {
+        let mut m = HashMap::with_capacity(1000);
+        for i in 0..500u64 {
+            m.insert(i, i * 3);
+        }
+
+        let seq: u64 = m.iter().map(|(k, v)| k + v).sum();
+        let par: u64 = m.par_iter().map(|(k, v)| k + v).sum();
+        assert_eq!(par, seq);
+
+        let seq: u64 = m.values().sum();
+        assert_eq!(m.par_values().sum::<u64>(), seq);
+
+        m.par_values_mut().for_each(|v| *v += 1);
+        assert_eq!(m.par_values().sum::<u64>(), seq + 500);
+    }
+}
@@ -0,0 +1,87 @@
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash};
+use std::ops::{Deref, DerefMut};
+
+use super::{HashMap, SeededState};
+
+// A mutable borrow of a present value, constructed from `HashMap::peek_mut`. It derefs to the
+// value, and `remove` deletes the entry without probing for the key again, e.g. to evict a
+// cache entry after looking at it
+pub struct PeekMut<'a, K: 'a, V: 'a, S = SeededState> {
+    map: &'a mut HashMap<K, V, S>,
+    index: usize,
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub fn peek_mut<Q>(&mut self, key: &Q) -> Option<PeekMut<'_, K, V, S>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let index = self.probe_key_bucket(key)?;
+        Some(PeekMut { map: self, index })
+    }
+}
+
+impl<K, V, S> PeekMut<'_, K, V, S> {
+    // Returns a reference to the key stored in the map
+    pub fn key(&self) -> &K {
+        self.map.buckets[self.index].key().unwrap()
+    }
+
+    // Removes the entry like `HashMap::remove` and returns the value
+    pub fn remove(self) -> V {
+        let pos = self.map.occupied_at[self.index];
+        self.map.remove_at(pos, true).1
+    }
+}
+
+impl<K, V, S> Deref for PeekMut<'_, K, V, S> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        self.map.buckets[self.index].value().unwrap()
+    }
+}
+
+impl<K, V, S> DerefMut for PeekMut<'_, K, V, S> {
+    fn deref_mut(&mut self) -> &mut V {
+        self.map.buckets[self.index].value_mut().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::HashMap;
+
+    #[test]
+    fn test_peek_mut() {
+        let mut m = HashMap::new();
+        for i in 0..20u32 {
+            m.insert_std(i, i * 10);
+        }
+
+        // keep and update
+        let mut hits = m.peek_mut(&3).unwrap();
+        assert_eq!(*hits.key(), 3);
+        *hits += 1;
+        assert_eq!(m.get(&3), Some(&31));
+
+        // look, then evict
+        let peeked = m.peek_mut(&7).unwrap();
+        if *peeked > 50 {
+            assert_eq!(peeked.remove(), 70);
+        }
+        assert!(!m.contains_key(&7));
+        assert_eq!(m.len(), 19);
+        assert!(m.peek_mut(&7).is_none());
+        for i in (0..20u32).filter(|&i| i != 7) {
+            assert!(m.contains_key(&i));
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use std::borrow::Borrow;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+
+use super::{HashMap, SeededState, Slot};
+
+// The average number of keys per displacement bucket
+const KEYS_PER_BUCKET: usize = 5;
+// How many seeds `into_perfect` tries before giving up. Keys with equal hashes are ruled out
+// before, no seed could separate them as the seed is mixed into the stored hashes
+const MAX_SEEDS: u64 = 64;
+
+// An immutable map built once by `HashMap::into_perfect`, for static dictionaries. The keys are
+// placed with a minimal perfect hash (CHD, compress-hash-displace): the entries fill a table of
+// exactly `len` slots, and every key has its own slot. A lookup picks the displacement of the
+// key's bucket, computes the slot and compares one key, there is no collision chain to walk
+pub struct PerfectHashMap<K, V, S = SeededState> {
+    entries: Vec<(K, V)>,
+    // the displacement `(d1, d2)` of every bucket
+    disps: Vec<(u32, u32)>,
+    // mixed into the hashes, tried until every bucket found a displacement
+    seed: u64,
+    hash_builder: S,
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    // Builds a `PerfectHashMap` of the entries with the same hasher. Finding the perfect hash
+    // takes a while for large maps, it's meant to be done once. The stored hashes are reused,
+    // no key is hashed again, so keys are only told apart by their 64-bit hashes: when two keys
+    // hash equally, e.g. with a weak `BuildHasher`, no perfect hash exists and the map is
+    // given back unchanged in the error
+    pub fn into_perfect(self) -> Result<PerfectHashMap<K, V, S>, NoPerfectHash<K, V, S>> {
+        let hashes: Vec<u64> = self.occupied.iter().map(|&i| self.hashes[i]).collect();
+        let mut sorted = hashes.clone();
+        sorted.sort_unstable();
+        if sorted.windows(2).any(|w| w[0] == w[1]) {
+            return Err(NoPerfectHash(Box::new(self)));
+        }
+        let Some((seed, chd)) =
+            (0..MAX_SEEDS).find_map(|seed| build_chd(&hashes, seed).map(|chd| (seed, chd)))
+        else {
+            return Err(NoPerfectHash(Box::new(self)));
+        };
+
+        // the positions in `hashes` are positions in the occupied index
+        let mut buckets = self.buckets;
+        let mut pairs: Vec<Option<(K, V)>> = self
+            .occupied
+            .iter()
+            .map(|&index| std::mem::replace(&mut buckets[index], Slot::Empty).into_pair())
+            .collect();
+        let entries = chd
+            .slots
+            .into_iter()
+            .map(|pos| pairs[pos].take().unwrap())
+            .collect();
+        Ok(PerfectHashMap {
+            entries,
+            disps: chd.disps,
+            seed,
+            hash_builder: self.hash_builder,
+        })
+    }
+}
+
+// The error of `into_perfect`, holds the map, which has keys with equal hashes
+pub struct NoPerfectHash<K, V, S = SeededState>(pub Box<HashMap<K, V, S>>);
+
+// Implemented by hand, the map itself isn't `Debug`
+impl<K, V, S> fmt::Debug for NoPerfectHash<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NoPerfectHash({} entries)", self.0.length)
+    }
+}
+
+impl<K, V, S> fmt::Display for NoPerfectHash<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("no perfect hash found, keys have equal hashes")
+    }
+}
+
+impl<K, V, S> std::error::Error for NoPerfectHash<K, V, S> {}
+
+impl<K, V, S> PerfectHashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        if self.entries.is_empty() {
+            return None;
+        }
+        let hashes = split_hash(self.hash_builder.hash_one(key), self.seed);
+        let disp = self.disps[hashes.bucket as usize % self.disps.len()];
+        let (ref k, ref v) = self.entries[slot(hashes, disp, self.entries.len())];
+        (k.borrow() == key).then_some(v)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Hashes {
+    bucket: u32,
+    f1: u32,
+    f2: u32,
+}
+
+// Derives the bucket and the two slot hashes of CHD from the map's hash of a key
+fn split_hash(hash: u64, seed: u64) -> Hashes {
+    let first = mix(hash ^ seed);
+    let second = mix(first);
+    Hashes {
+        bucket: (first >> 32) as u32,
+        f1: first as u32,
+        f2: second as u32,
+    }
+}
+
+// The finalizer of splitmix64, spreads the bits of `x` over the whole word
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+fn slot(hashes: Hashes, (d1, d2): (u32, u32), len: usize) -> usize {
+    let mixed = d2
+        .wrapping_add(hashes.f1.wrapping_mul(d1))
+        .wrapping_add(hashes.f2);
+    mixed as usize % len
+}
+
+struct Chd {
+    disps: Vec<(u32, u32)>,
+    // for every slot, the position in `hashes` of the key placed there
+    slots: Vec<usize>,
+}
+
+// Places the keys of `hashes` with `seed`: the keys are grouped into buckets, and the buckets,
+// largest first, search a displacement which moves all their keys into free slots.
+// `None` when a bucket finds no displacement, e.g. two of its keys got the same slot hashes
+fn build_chd(hashes: &[u64], seed: u64) -> Option<Chd> {
+    let len = hashes.len();
+    let bucket_count = len.div_ceil(KEYS_PER_BUCKET).max(1);
+    let split: Vec<Hashes> = hashes.iter().map(|&h| split_hash(h, seed)).collect();
+
+    let mut buckets = vec![Vec::new(); bucket_count];
+    for (pos, hashes) in split.iter().enumerate() {
+        buckets[hashes.bucket as usize % bucket_count].push(pos);
+    }
+    let mut order: Vec<usize> = (0..bucket_count).collect();
+    order.sort_by_key(|&b| std::cmp::Reverse(buckets[b].len()));
+
+    let mut disps = vec![(0, 0); bucket_count];
+    let mut slots: Vec<Option<usize>> = vec![None; len];
+    let mut candidate = Vec::new();
+    for b in order {
+        let keys = &buckets[b];
+        if keys.is_empty() {
+            break;
+        }
+        let found = (0..len as u32)
+            .flat_map(|d1| (0..len as u32).map(move |d2| (d1, d2)))
+            .find(|&disp| {
+                candidate.clear();
+                keys.iter().all(|&pos| {
+                    let index = slot(split[pos], disp, len);
+                    let free = slots[index].is_none() && !candidate.contains(&index);
+                    candidate.push(index);
+                    free
+                })
+            })?;
+        disps[b] = found;
+        for (&pos, &index) in keys.iter().zip(&candidate) {
+            slots[index] = Some(pos);
+        }
+    }
+    Some(Chd {
+        disps,
+        slots: slots.into_iter().map(Option::unwrap).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{HashMap, NoPerfectHash};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_into_perfect() {
+        let mut m = HashMap::with_load_factor(0.75);
+        for i in 0..300u32 {
+            m.insert(format!("word{}", i), i);
+        }
+
+        let perfect = Arc::new(m.into_perfect().unwrap());
+        assert_eq!(perfect.len(), 300);
+        assert_eq!(perfect.entries.len(), 300);
+        std::thread::scope(|scope| {
+            for _ in 0..2 {
+                let perfect = Arc::clone(&perfect);
+                scope.spawn(move || {
+                    for i in 0..300u32 {
+                        assert_eq!(perfect.get(&format!("word{}", i)), Some(&i));
+                    }
+                    for i in 300..1000u32 {
+                        assert!(!perfect.contains_key(&format!("word{}", i)));
+                    }
+                });
+            }
+        });
+        let mut values: Vec<_> = perfect.iter().map(|(_, &v)| v).collect();
+        values.sort_unstable();
+        assert_eq!(values, (0..300).collect::<Vec<_>>());
+
+        let empty = HashMap::<u32, u32>::new().into_perfect().unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(empty.get(&1), None);
+    }
+
+    #[test]
+    fn test_into_perfect_equal_hashes() {
+        use std::hash::{BuildHasherDefault, Hasher};
+
+        // hashes only the low byte of an integer
+        #[derive(Default)]
+        struct LowByte(u64);
+
+        impl Hasher for LowByte {
+            fn finish(&self) -> u64 {
+                self.0
+            }
+            fn write(&mut self, _: &[u8]) {
+                unimplemented!()
+            }
+            fn write_u32(&mut self, n: u32) {
+                self.0 = (n & 0xff) as u64;
+            }
+        }
+
+        let mut m =
+            HashMap::with_capacity_and_hasher(512, BuildHasherDefault::<LowByte>::default());
+        for i in 0..300u32 {
+            m.insert(i, i);
+        }
+        // 256 and 0 hash equally, the map comes back intact without searching any seed
+        let start = Instant::now();
+        let Err(NoPerfectHash(m)) = m.into_perfect() else {
+            panic!("keys with equal hashes got a perfect hash");
+        };
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(m.len(), 300);
+        assert_eq!(m.get(&256), Some(&256));
+
+        let mut distinct =
+            HashMap::with_capacity_and_hasher(512, BuildHasherDefault::<LowByte>::default());
+        for i in 0..200u32 {
+            distinct.insert(i, i);
+        }
+        assert!(distinct.into_perfect().is_ok());
+    }
+}
@@ -0,0 +1,174 @@
+use std::borrow::Borrow;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use super::{HashMap, DEFAULT_LOAD_FACTOR};
+
+// The time source of a `TtlHashMap`, so tests can advance time without sleeping
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+// The wall clock, `Instant::now`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+}
+
+// A map whose entries expire `ttl` after they were inserted, e.g. for a time based cache.
+// Lookups treat an expired entry as absent and remove it, `purge_expired` removes all of them
+pub struct TtlHashMap<K, V, C = SystemClock> {
+    // every value with the time it was inserted
+    map: HashMap<K, (V, Instant)>,
+    ttl: Duration,
+    clock: C,
+}
+
+impl<K, V> TtlHashMap<K, V>
+where
+    K: Hash + Eq,
+{
+    pub fn new(ttl: Duration) -> TtlHashMap<K, V> {
+        TtlHashMap::with_clock(ttl, SystemClock)
+    }
+}
+
+impl<K, V, C> TtlHashMap<K, V, C>
+where
+    K: Hash + Eq,
+    C: Clock,
+{
+    pub fn with_clock(ttl: Duration, clock: C) -> TtlHashMap<K, V, C> {
+        TtlHashMap {
+            map: HashMap::with_load_factor(DEFAULT_LOAD_FACTOR),
+            ttl,
+            clock,
+        }
+    }
+
+    // The number of entries, including expired ones which weren't removed yet
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    // Inserts the value, which expires `ttl` from now. Returns the previous value of the key
+    // unless it already expired
+    pub fn insert(&mut self, key: K, val: V) -> Option<V> {
+        let now = self.clock.now();
+        let (old, inserted) = self.map.insert_std(key, (val, now))?;
+        (!self.expired(inserted, now)).then_some(old)
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let now = self.clock.now();
+        let inserted = self.map.get(key)?.1;
+        if self.expired(inserted, now) {
+            self.map.remove(key);
+            return None;
+        }
+        self.map.get(key).map(|(val, _)| val)
+    }
+
+    pub fn contains_key<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.get(key).is_some()
+    }
+
+    // Removes the key and returns its value unless it already expired
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let now = self.clock.now();
+        let (val, inserted) = self.map.peek_mut(key)?.remove();
+        (!self.expired(inserted, now)).then_some(val)
+    }
+
+    // Removes all expired entries, returns how many
+    pub fn purge_expired(&mut self) -> usize {
+        let now = self.clock.now();
+        let ttl = self.ttl;
+        self.map
+            .retain_count(|_, (_, inserted)| now.saturating_duration_since(*inserted) < ttl)
+    }
+
+    #[inline]
+    fn expired(&self, inserted: Instant, now: Instant) -> bool {
+        now.saturating_duration_since(inserted) >= self.ttl
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{Clock, TtlHashMap};
+    use std::cell::Cell;
+    use std::time::{Duration, Instant};
+
+    struct MockClock(Cell<Instant>);
+
+    impl MockClock {
+        fn advance(&self, by: Duration) {
+            self.0.set(self.0.get() + by);
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> Instant {
+            self.0.get()
+        }
+    }
+
+    #[test]
+    fn test_ttl() {
+        let clock = MockClock(Cell::new(Instant::now()));
+        let mut m = TtlHashMap::with_clock(Duration::from_secs(10), &clock);
+        m.insert("a", 1);
+        clock.advance(Duration::from_secs(6));
+        m.insert("b", 2);
+        assert_eq!(m.get("a"), Some(&1));
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(m.get("a"), None);
+        assert_eq!(m.len(), 1);
+        assert!(m.contains_key("b"));
+
+        // expired values aren't reported as replaced or removed
+        assert_eq!(m.insert("b", 3), Some(2));
+        m.insert("c", 4);
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(m.insert("b", 5), None);
+        assert_eq!(m.remove("b"), Some(5));
+        assert_eq!(m.remove("c"), None);
+
+        m.insert("d", 6);
+        m.insert("e", 7);
+        clock.advance(Duration::from_secs(10));
+        m.insert("f", 8);
+        assert_eq!(m.purge_expired(), 2);
+        assert_eq!(m.len(), 1);
+        assert_eq!(m.get("f"), Some(&8));
+    }
+}
@@ -0,0 +1,100 @@
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash};
+
+use super::{HashMap, HashMapIter, SeededState};
+
+/// A read-only borrow of a map, constructed from `HashMap::view`. It only offers lookups and
+/// iteration, so it can be handed across an API boundary, or to reader threads, without
+/// exposing any mutation:
+///
+/// ```compile_fail
+/// let mut map = hashmap::HashMap::new();
+/// map.insert(1, 1);
+/// let view = map.view();
+/// view.get_mut(&1);
+/// ```
+pub struct View<'a, K: 'a, V: 'a, S = SeededState> {
+    map: &'a HashMap<K, V, S>,
+}
+
+// Implemented by hand, deriving would require `K`, `V` and `S` to be `Clone`
+impl<K, V, S> Clone for View<'_, K, V, S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K, V, S> Copy for View<'_, K, V, S> {}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub fn view(&self) -> View<'_, K, V, S> {
+        View { map: self }
+    }
+}
+
+impl<'a, K, V, S> View<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub fn get<Q>(&self, key: &Q) -> Option<&'a V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.map.get(key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.map.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn iter(&self) -> HashMapIter<'a, K, V> {
+        self.map.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::HashMap;
+
+    #[test]
+    fn test_view() {
+        let mut m = HashMap::new();
+        for i in 0..10u32 {
+            m.insert(i, i * 2);
+        }
+
+        let view = m.view();
+        assert_eq!(view.len(), 10);
+        assert_eq!(view.get(&4), Some(&8));
+        assert!(!view.contains_key(&10));
+
+        // copies of the view read concurrently
+        std::thread::scope(|scope| {
+            let readers: Vec<_> = (0..4)
+                .map(|_| scope.spawn(move || view.iter().map(|(_, &v)| v).sum::<u32>()))
+                .collect();
+            for reader in readers {
+                assert_eq!(reader.join().unwrap(), 90);
+            }
+        });
+    }
+}